@@ -0,0 +1,142 @@
+//! Locating the configuration file and resolving the `{PluginPath}` and `{RetroBatPath}`
+//! placeholders of the paths it holds.
+
+use crate::registry;
+use log::{info, warn};
+use serde::{de::{self, Deserializer}, Deserialize};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the configuration file, used when no path is given explicitly.
+pub const CONFIG_ENV_VAR: &str = "MARQUEE_MANAGER_CONFIG";
+/// Name of the configuration file looked for next to the executable.
+pub const CONFIG_FILE_NAME: &str = "config.ini";
+/// The mpv IPC pipe used when config.ini sets no `IPCChannel`.
+pub const DEFAULT_IPC_CHANNEL: &str = r"\\.\pipe\mpv-pipe";
+
+/// The configuration file to use: `explicit` (e.g. a `--config` argument), then the
+/// `MARQUEE_MANAGER_CONFIG` environment variable, then config.ini next to the executable.
+pub fn locate_config(explicit: Option<&Path>) -> PathBuf {
+    let chosen = explicit
+        .map(Path::to_path_buf)
+        .or_else(|| env::var_os(CONFIG_ENV_VAR).map(PathBuf::from));
+    match chosen {
+        Some(path) => std::path::absolute(&path).unwrap_or(path),
+        None => executable_dir().join(CONFIG_FILE_NAME),
+    }
+}
+
+/// The folder of the running executable, or the working directory if it cannot be found.
+pub fn executable_dir() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .or_else(|| env::current_dir().ok())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// The plugin folder relative paths are resolved against: the folder holding the
+/// configuration file.
+pub fn plugin_path(config_path: &Path) -> std::io::Result<PathBuf> {
+    match config_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        Some(parent) => Ok(parent.to_path_buf()),
+        None => env::current_dir(),
+    }
+}
+
+/// `configured` if set, otherwise the install path RetroBat records in the Windows registry.
+pub fn find_retrobat_path(configured: Option<PathBuf>) -> Option<PathBuf> {
+    if configured.is_some() {
+        return configured;
+    }
+    warn!("RetroBatPath not found in config.ini. Attempting to read from Windows Registry...");
+    let path_str = registry::get_retrobat_path()?;
+    info!("Found RetroBat path in registry: {}", path_str);
+    Some(PathBuf::from(path_str))
+}
+
+/// Expands `{PluginPath}` and `{RetroBatPath}` in `path` and makes it relative to the plugin
+/// folder. Fails with the name of a placeholder that has no value.
+pub fn resolve_path(path: &Path, retrobat_path: Option<&Path>, plugin_path: &Path) -> Result<PathBuf, &'static str> {
+    // Placeholders only come from config.ini, which is valid Unicode
+    let Some(text) = path.to_str() else {
+        return Ok(plugin_path.join(path));
+    };
+    let mut expanded = text.replace("{PluginPath}", &plugin_path.to_string_lossy());
+    if expanded.contains("{RetroBatPath}") {
+        let retrobat_path = retrobat_path.ok_or("RetroBatPath")?;
+        expanded = expanded.replace("{RetroBatPath}", &retrobat_path.to_string_lossy());
+    }
+    Ok(plugin_path.join(expanded))
+}
+
+/// Deserializes the `true`/`false` strings of an ini file, for `deserialize_with`.
+pub fn deserialize_bool_from_string<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = String::deserialize(deserializer)?;
+    match s.to_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(de::Error::unknown_variant(&s, &["true", "false"])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::{value, IntoDeserializer};
+
+    fn parse_bool(text: &str) -> Result<bool, value::Error> {
+        deserialize_bool_from_string(text.into_deserializer())
+    }
+
+    #[test]
+    fn explicit_config_path_comes_first() {
+        let explicit = executable_dir().join("custom.ini");
+        assert_eq!(locate_config(Some(&explicit)), explicit);
+    }
+
+    #[test]
+    fn plugin_path_is_the_config_folder() {
+        let config = Path::new("plugins").join("marquee").join(CONFIG_FILE_NAME);
+        assert_eq!(plugin_path(&config).unwrap(), Path::new("plugins").join("marquee"));
+        assert_eq!(plugin_path(Path::new(CONFIG_FILE_NAME)).unwrap(), env::current_dir().unwrap());
+    }
+
+    #[test]
+    fn resolve_path_expands_placeholders() {
+        let plugin = Path::new("plugin");
+        let retrobat = Path::new("retrobat");
+        assert_eq!(resolve_path(Path::new("images"), None, plugin).unwrap(), plugin.join("images"));
+        assert_eq!(
+            resolve_path(Path::new("{PluginPath}/images"), None, plugin).unwrap(),
+            plugin.join("plugin/images")
+        );
+        assert_eq!(
+            resolve_path(Path::new("{RetroBatPath}/roms"), Some(retrobat), plugin).unwrap(),
+            plugin.join("retrobat/roms")
+        );
+    }
+
+    #[test]
+    fn resolve_path_names_the_missing_placeholder() {
+        assert_eq!(resolve_path(Path::new("{RetroBatPath}/roms"), None, Path::new("plugin")), Err("RetroBatPath"));
+    }
+
+    #[test]
+    fn configured_retrobat_path_skips_the_registry() {
+        let configured = PathBuf::from("retrobat");
+        assert_eq!(find_retrobat_path(Some(configured.clone())), Some(configured));
+    }
+
+    #[test]
+    fn bools_ignore_case() {
+        assert_eq!(parse_bool("true"), Ok(true));
+        assert_eq!(parse_bool("False"), Ok(false));
+        assert_eq!(parse_bool("TRUE"), Ok(true));
+        assert!(parse_bool("yes").is_err());
+        assert!(parse_bool("").is_err());
+    }
+}
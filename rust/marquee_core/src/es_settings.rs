@@ -0,0 +1,185 @@
+//! Reading the settings EmulationStation saves in es_settings.cfg, which fill in what
+//! config.ini leaves out.
+
+use crate::config;
+use log::{info, warn};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The settings file of EmulationStation, in its `.emulationstation` folder.
+pub const ES_SETTINGS_FILE: &str = "es_settings.cfg";
+
+/// Where RetroBat's EmulationStation keeps es_settings.cfg.
+pub fn default_path(retrobat_path: &Path) -> PathBuf {
+    retrobat_path.join("emulationstation").join(".emulationstation").join(ES_SETTINGS_FILE)
+}
+
+/// Where es_settings.cfg is: the `ESSettingsPath` setting when there is one, resolved like the
+/// other paths of config.ini, else [`default_path`]. Errors with the name of the placeholder
+/// that cannot be expanded, like [`config::resolve_path`].
+pub fn resolve_path(setting: Option<&Path>, retrobat_path: Option<&Path>, plugin_path: &Path) -> Result<PathBuf, &'static str> {
+    match setting {
+        Some(path) => config::resolve_path(path, retrobat_path, plugin_path),
+        None => retrobat_path.map(default_path).ok_or("RetroBatPath"),
+    }
+}
+
+/// A login saved by the frontend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: Option<String>,
+}
+
+/// The settings the frontend saves: `<bool>`, `<int>` and `<string>` elements with a `name`
+/// and a `value`, all kept as text.
+#[derive(Debug, Clone, Default)]
+pub struct EsSettings {
+    values: HashMap<String, String>,
+}
+
+impl EsSettings {
+    pub fn parse(content: &str) -> Result<Self, quick_xml::Error> {
+        let mut reader = Reader::from_str(content);
+        reader.trim_text(true);
+        let mut values = HashMap::new();
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf)? {
+                Event::Start(ref element) | Event::Empty(ref element) => {
+                    let (mut name, mut value) = (None, None);
+                    for attribute in element.attributes() {
+                        let attribute = attribute?;
+                        match attribute.key {
+                            b"name" => name = Some(attribute.unescape_and_decode_value(&reader)?),
+                            b"value" => value = Some(attribute.unescape_and_decode_value(&reader)?),
+                            _ => {}
+                        }
+                    }
+                    if let (Some(name), Some(value)) = (name, value) {
+                        values.insert(name, value);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+        Ok(EsSettings { values })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::parse(&fs::read_to_string(path)?)?)
+    }
+
+    /// The settings at `path`, or none when the file is missing or invalid, so the frontend
+    /// settings only ever fill in what config.ini leaves out.
+    pub fn load_or_default(path: &Path) -> Self {
+        if !path.is_file() {
+            info!("No frontend settings at {:?}", path);
+            return Self::default();
+        }
+        Self::load(path).unwrap_or_else(|e| {
+            warn!("Failed to read the frontend settings {:?}: {}", path, e);
+            Self::default()
+        })
+    }
+
+    /// The value of the setting `name`, None when it is missing or empty.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str).filter(|value| !value.is_empty())
+    }
+
+    /// The interface language, e.g. `fr_FR`.
+    pub fn language(&self) -> Option<&str> {
+        self.get("Language")
+    }
+
+    /// The folder name of the selected theme.
+    pub fn theme(&self) -> Option<&str> {
+        self.get("ThemeSet")
+    }
+
+    pub fn retroachievements(&self) -> Option<Credentials> {
+        self.credentials("global.retroachievements.username", "global.retroachievements.password")
+    }
+
+    pub fn screenscraper(&self) -> Option<Credentials> {
+        self.credentials("ScreenScraperUser", "ScreenScraperPass")
+    }
+
+    fn credentials(&self, username: &str, password: &str) -> Option<Credentials> {
+        Some(Credentials {
+            username: self.get(username)?.to_string(),
+            password: self.get(password).map(str::to_string),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: &str = r#"<?xml version="1.0"?>
+<config>
+    <bool name="DrawFramerate" value="false" />
+    <string name="Language" value="fr_FR" />
+    <string name="ThemeSet" value="es-theme-carbon" />
+    <string name="ScreenScraperUser" value="ada" />
+    <string name="ScreenScraperPass" value="" />
+    <string name="global.retroachievements.username" value="grace" />
+    <string name="global.retroachievements.password" value="p&amp;ss" />
+    <int name="ScreenSaverTime" value="300000" />
+</config>
+"#;
+
+    #[test]
+    fn parse_reads_every_named_value() {
+        let settings = EsSettings::parse(SETTINGS).unwrap();
+        assert_eq!(settings.get("DrawFramerate"), Some("false"));
+        assert_eq!(settings.get("ScreenSaverTime"), Some("300000"));
+        assert_eq!(settings.language(), Some("fr_FR"));
+        assert_eq!(settings.theme(), Some("es-theme-carbon"));
+        assert_eq!(settings.get("Missing"), None);
+    }
+
+    #[test]
+    fn empty_values_count_as_missing() {
+        let settings = EsSettings::parse(SETTINGS).unwrap();
+        assert_eq!(settings.get("ScreenScraperPass"), None);
+        assert_eq!(EsSettings::default().language(), None);
+    }
+
+    #[test]
+    fn credentials_need_a_username() {
+        let settings = EsSettings::parse(SETTINGS).unwrap();
+        assert_eq!(
+            settings.retroachievements(),
+            Some(Credentials { username: "grace".to_string(), password: Some("p&ss".to_string()) })
+        );
+        assert_eq!(settings.screenscraper(), Some(Credentials { username: "ada".to_string(), password: None }));
+        assert_eq!(EsSettings::parse("<config />").unwrap().screenscraper(), None);
+    }
+
+    #[test]
+    fn invalid_files_are_errors_but_default_when_loaded() {
+        assert!(EsSettings::parse("<config><string name=\"a\" value=\"b\"></config>").is_err());
+        assert_eq!(EsSettings::load_or_default(Path::new("missing/es_settings.cfg")).language(), None);
+    }
+
+    #[test]
+    fn resolve_path_prefers_the_setting() {
+        let plugin = Path::new("plugin");
+        let retrobat = Path::new("retrobat");
+        assert_eq!(
+            resolve_path(Some(Path::new("{RetroBatPath}/custom.cfg")), Some(retrobat), plugin).unwrap(),
+            plugin.join("retrobat/custom.cfg")
+        );
+        assert_eq!(resolve_path(None, Some(retrobat), plugin).unwrap(), default_path(retrobat));
+        assert!(default_path(retrobat).ends_with(Path::new(".emulationstation").join(ES_SETTINGS_FILE)));
+        assert_eq!(resolve_path(None, None, plugin), Err("RetroBatPath"));
+    }
+}
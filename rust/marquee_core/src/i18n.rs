@@ -0,0 +1,110 @@
+//! Localized user-facing messages, read from ini catalogs with a `[Messages]` section.
+
+use crate::ini::Document;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// The language used for messages missing from the selected catalog.
+pub const FALLBACK_LANGUAGE: &str = "en";
+// Catalogs are ini files with every message under this section, as `key = text`
+const SECTION: &str = "Messages";
+
+/// The catalogs of the calling crate, the `en.ini` and `fr.ini` files of its `locales` folder,
+/// as pairs for [`Catalog::new`]. They are built into the executable, so messages never go
+/// missing with a partial install.
+#[macro_export]
+macro_rules! builtin_catalogs {
+    () => {
+        &[
+            ("en", include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/locales/en.ini"))),
+            ("fr", include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/locales/fr.ini"))),
+        ]
+    };
+}
+
+/// The user-facing messages of one language, falling back to English for the ones it lacks.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+// "fr", "FR", "fr_FR" and "fr-FR" all select the French catalog
+fn primary_subtag(language: &str) -> String {
+    language.trim().split(['_', '-']).next().unwrap_or_default().to_lowercase()
+}
+
+fn parse(content: &str) -> HashMap<String, String> {
+    let document = Document::parse(content);
+    document
+        .keys(SECTION)
+        .into_iter()
+        .filter_map(|key| Some((key.to_string(), document.get(SECTION, key)?.to_string())))
+        .collect()
+}
+
+impl Catalog {
+    /// Selects the catalog of `language` among `catalogs`, pairs of a language code and the
+    /// catalog content, which should include one for [`FALLBACK_LANGUAGE`].
+    pub fn new(language: &str, catalogs: &[(&str, &str)]) -> Self {
+        let find = |language: &str| {
+            catalogs
+                .iter()
+                .find(|(code, _)| *code == language)
+                .map(|(_, content)| parse(content))
+                .unwrap_or_default()
+        };
+        Catalog { messages: find(&primary_subtag(language)), fallback: find(FALLBACK_LANGUAGE) }
+    }
+
+    /// The message for `key`, or the key itself when no catalog has it.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.messages.get(key).or_else(|| self.fallback.get(key)).map(String::as_str).unwrap_or(key)
+    }
+
+    /// The message for `key` with its `{name}` placeholders replaced by `args`.
+    pub fn format(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        args.iter()
+            .fold(self.get(key).to_string(), |message, (name, value)| {
+                message.replace(&format!("{{{}}}", name), &value.to_string())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOGS: &[(&str, &str)] = &[
+        ("en", "[Messages]\ngreeting = Hello {name}\nfarewell = Goodbye\n"),
+        ("fr", "; French\n[Messages]\ngreeting = Bonjour {name}\n"),
+    ];
+
+    #[test]
+    fn language_codes_select_by_primary_subtag() {
+        for language in ["fr", "FR", "fr_FR", "fr-FR", " fr "] {
+            assert_eq!(Catalog::new(language, CATALOGS).get("greeting"), "Bonjour {name}", "{}", language);
+        }
+    }
+
+    #[test]
+    fn missing_messages_fall_back_to_english() {
+        let catalog = Catalog::new("fr", CATALOGS);
+        assert_eq!(catalog.get("farewell"), "Goodbye");
+        let catalog = Catalog::new("de_DE", CATALOGS);
+        assert_eq!(catalog.get("greeting"), "Hello {name}");
+    }
+
+    #[test]
+    fn unknown_keys_are_returned_as_they_are() {
+        assert_eq!(Catalog::new("en", CATALOGS).get("missing"), "missing");
+        assert_eq!(Catalog::default().get("greeting"), "greeting");
+    }
+
+    #[test]
+    fn format_replaces_named_placeholders() {
+        let catalog = Catalog::new("fr", CATALOGS);
+        assert_eq!(catalog.format("greeting", &[("name", &"Ada")]), "Bonjour Ada");
+        assert_eq!(catalog.format("greeting", &[("other", &1)]), "Bonjour {name}");
+    }
+}
@@ -0,0 +1,333 @@
+//! Reading and editing ini files while keeping them as the user wrote them.
+
+use std::fmt;
+
+/// An ini file that can be edited without losing its comments, blank lines, key order or
+/// unknown keys: lines are kept as written and only the edited ones are rewritten.
+#[derive(Debug, Clone)]
+pub struct Document {
+    lines: Vec<Line>,
+    newline: &'static str,
+    trailing_newline: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Line {
+    /// A comment, a blank line or anything else that is not a section or a key.
+    Other(String),
+    Section { name: String, text: String },
+    Entry { key: String, value: String, text: String },
+}
+
+impl Line {
+    fn text(&self) -> &str {
+        match self {
+            Line::Other(text) | Line::Section { text, .. } | Line::Entry { text, .. } => text,
+        }
+    }
+}
+
+fn is_comment(line: &str) -> bool {
+    line.starts_with(';') || line.starts_with('#')
+}
+
+impl Document {
+    pub fn parse(content: &str) -> Self {
+        let lines = content
+            .lines()
+            .map(|text| {
+                let trimmed = text.trim();
+                if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                    let name = trimmed[1..trimmed.len() - 1].trim().to_string();
+                    return Line::Section { name, text: text.to_string() };
+                }
+                match trimmed.split_once('=') {
+                    Some((key, value)) if !is_comment(trimmed) => Line::Entry {
+                        key: key.trim().to_string(),
+                        value: value.trim().to_string(),
+                        text: text.to_string(),
+                    },
+                    _ => Line::Other(text.to_string()),
+                }
+            })
+            .collect();
+        Document {
+            lines,
+            newline: if content.contains("\r\n") { "\r\n" } else { "\n" },
+            trailing_newline: content.is_empty() || content.ends_with('\n'),
+        }
+    }
+
+    /// The section names in file order, without duplicates.
+    pub fn sections(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for line in &self.lines {
+            if let Line::Section { name, .. } = line {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// The keys of `section` in file order.
+    pub fn keys(&self, section: &str) -> Vec<&str> {
+        self.entries(section).map(|index| self.key_at(index)).collect()
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        let index = self.find(section, key)?;
+        match &self.lines[index] {
+            Line::Entry { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Changes the value of `key`, or adds it after the last key of `section`, creating the
+    /// section at the end of the file if needed.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let line = Line::Entry { key: key.to_string(), value: value.to_string(), text: format!("{} = {}", key, value) };
+        match self.find(section, key) {
+            Some(index) => self.lines[index] = line,
+            None => {
+                let index = self.end_of_section(section);
+                self.lines.insert(index, line);
+            }
+        }
+    }
+
+    /// Adds a comment line above `key`. Returns false if `key` is missing.
+    pub fn comment_before(&mut self, section: &str, key: &str, comment: &str) -> bool {
+        let Some(index) = self.find(section, key) else {
+            return false;
+        };
+        self.lines.insert(index, Line::Other(format!("; {}", comment)));
+        true
+    }
+
+    /// Removes `key`, returning its value.
+    pub fn remove(&mut self, section: &str, key: &str) -> Option<String> {
+        let index = self.find(section, key)?;
+        match self.lines.remove(index) {
+            Line::Entry { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Renames `from` to `to` where it stands, keeping its value. Returns false if `from` is
+    /// missing or `to` already exists.
+    pub fn rename(&mut self, section: &str, from: &str, to: &str) -> bool {
+        if self.find(section, to).is_some() {
+            return false;
+        }
+        let Some(index) = self.find(section, from) else {
+            return false;
+        };
+        let value = self.get(section, from).unwrap_or_default().to_string();
+        self.lines[index] = Line::Entry { key: to.to_string(), text: format!("{} = {}", to, value), value };
+        true
+    }
+
+    /// Turns the line of `key` into a comment starting with `note`, so its value is kept for
+    /// reference but no longer read.
+    pub fn comment_out(&mut self, section: &str, key: &str, note: &str) -> bool {
+        let Some(index) = self.find(section, key) else {
+            return false;
+        };
+        let text = format!("; {}: {}", note, self.lines[index].text().trim());
+        self.lines[index] = Line::Other(text);
+        true
+    }
+
+    fn key_at(&self, index: usize) -> &str {
+        match &self.lines[index] {
+            Line::Entry { key, .. } => key,
+            _ => "",
+        }
+    }
+
+    // Indexes of the entry lines of `section`, keys before any section header being in ""
+    fn entries<'a>(&'a self, section: &'a str) -> impl Iterator<Item = usize> + 'a {
+        let mut current = "";
+        self.lines.iter().enumerate().filter_map(move |(index, line)| match line {
+            Line::Section { name, .. } => {
+                current = name;
+                None
+            }
+            Line::Entry { .. } if current == section => Some(index),
+            _ => None,
+        })
+    }
+
+    fn find(&self, section: &str, key: &str) -> Option<usize> {
+        self.entries(section).find(|&index| self.key_at(index) == key)
+    }
+
+    // Where a new line of `section` goes: after its last key, so the comments introducing the
+    // next section stay with it
+    fn end_of_section(&mut self, section: &str) -> usize {
+        if let Some(last) = self.entries(section).last() {
+            return last + 1;
+        }
+        let header = self
+            .lines
+            .iter()
+            .position(|line| matches!(line, Line::Section { name, .. } if name == section));
+        match header {
+            Some(header) => header + 1,
+            None if section.is_empty() => 0,
+            None => {
+                if self.lines.last().is_some_and(|line| !line.text().trim().is_empty()) {
+                    self.lines.push(Line::Other(String::new()));
+                }
+                self.lines.push(Line::Section { name: section.to_string(), text: format!("[{}]", section) });
+                self.lines.len()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                f.write_str(self.newline)?;
+            }
+            f.write_str(line.text())?;
+        }
+        if self.trailing_newline && !self.lines.is_empty() {
+            f.write_str(self.newline)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "; Marquee Manager\n[Settings]\nMarqueeWidth = 1920\n; height\nMarqueeHeight=360\n\n[Output:Top]\nScreen = 1\n[Settings]\nLanguage = fr\n";
+
+    #[test]
+    fn sections_are_listed_once_in_file_order() {
+        assert_eq!(Document::parse(CONFIG).sections(), ["Settings", "Output:Top"]);
+    }
+
+    #[test]
+    fn keys_and_values_are_trimmed() {
+        let document = Document::parse(CONFIG);
+        assert_eq!(document.keys("Settings"), ["MarqueeWidth", "MarqueeHeight", "Language"]);
+        assert_eq!(document.get("Settings", "MarqueeHeight"), Some("360"));
+        assert_eq!(document.get("Output:Top", "Screen"), Some("1"));
+        assert_eq!(document.get("Settings", "Screen"), None);
+    }
+
+    #[test]
+    fn comments_are_not_keys() {
+        let document = Document::parse("[Settings]\n; Width = 1\n# Height = 2\n");
+        assert!(document.keys("Settings").is_empty());
+    }
+
+    #[test]
+    fn remove_returns_the_value() {
+        let mut document = Document::parse(CONFIG);
+        assert_eq!(document.remove("Settings", "MarqueeWidth"), Some("1920".to_string()));
+        assert_eq!(document.remove("Settings", "MarqueeWidth"), None);
+        assert_eq!(document.keys("Settings"), ["MarqueeHeight", "Language"]);
+    }
+
+    #[test]
+    fn display_gives_back_the_parsed_text() {
+        for content in [CONFIG, "", "\n", "[Settings]\nA = 1", "  ; indented\n[ Settings ]\n  A=1  \n\n\n"] {
+            assert_eq!(Document::parse(content).to_string(), content);
+        }
+    }
+
+    #[test]
+    fn set_changes_a_value_in_place() {
+        let mut document = Document::parse(CONFIG);
+        document.set("Settings", "MarqueeHeight", "480");
+        assert_eq!(document.to_string(), CONFIG.replace("MarqueeHeight=360", "MarqueeHeight = 480"));
+    }
+
+    #[test]
+    fn set_adds_keys_after_the_last_key_of_the_section() {
+        let mut document = Document::parse("[Settings]\nA = 1\n\n; Outputs\n[Output:Top]\nScreen = 1\n");
+        document.set("Settings", "B", "2");
+        assert_eq!(document.to_string(), "[Settings]\nA = 1\nB = 2\n\n; Outputs\n[Output:Top]\nScreen = 1\n");
+    }
+
+    #[test]
+    fn set_creates_a_missing_section_at_the_end() {
+        let mut document = Document::parse("[Settings]\nA = 1\n");
+        document.set("Commands", "game-start", "echo");
+        assert_eq!(document.to_string(), "[Settings]\nA = 1\n\n[Commands]\ngame-start = echo\n");
+        assert_eq!(document.get("Commands", "game-start"), Some("echo"));
+
+        let mut document = Document::parse("");
+        document.set("Settings", "A", "1");
+        assert_eq!(document.to_string(), "[Settings]\nA = 1\n");
+    }
+
+    #[test]
+    fn rename_keeps_the_position_and_value() {
+        let mut document = Document::parse(CONFIG);
+        assert!(document.rename("Settings", "MarqueeWidth", "Width"));
+        assert_eq!(document.keys("Settings"), ["Width", "MarqueeHeight", "Language"]);
+        assert_eq!(document.get("Settings", "Width"), Some("1920"));
+        assert_eq!(document.to_string(), CONFIG.replace("MarqueeWidth = 1920", "Width = 1920"));
+        assert!(!document.rename("Settings", "MarqueeWidth", "Other"));
+        assert!(!document.rename("Settings", "Width", "MarqueeHeight"));
+    }
+
+    #[test]
+    fn comment_out_keeps_the_line_for_reference() {
+        let mut document = Document::parse(CONFIG);
+        assert!(document.comment_out("Settings", "MarqueeHeight", "Obsolete"));
+        assert_eq!(document.get("Settings", "MarqueeHeight"), None);
+        assert_eq!(document.to_string(), CONFIG.replace("MarqueeHeight=360", "; Obsolete: MarqueeHeight=360"));
+        assert!(!document.comment_out("Settings", "MarqueeHeight", "Obsolete"));
+    }
+
+    #[test]
+    fn comment_before_inserts_a_line() {
+        let mut document = Document::parse("[Settings]\nA = 1\n");
+        assert!(document.comment_before("Settings", "A", "First"));
+        assert!(!document.comment_before("Settings", "B", "Missing"));
+        assert_eq!(document.to_string(), "[Settings]\n; First\nA = 1\n");
+    }
+
+    #[test]
+    fn keys_before_the_first_section_have_no_section() {
+        let mut document = Document::parse("; header\nA = 1\n[Settings]\nA = 2\n");
+        assert_eq!(document.keys(""), ["A"]);
+        assert_eq!(document.get("", "A"), Some("1"));
+        assert_eq!(document.get("Settings", "A"), Some("2"));
+        document.set("", "B", "3");
+        assert_eq!(document.to_string(), "; header\nA = 1\nB = 3\n[Settings]\nA = 2\n");
+
+        let mut document = Document::parse("[Settings]\nA = 2\n");
+        document.set("", "B", "3");
+        assert_eq!(document.to_string(), "B = 3\n[Settings]\nA = 2\n");
+    }
+
+    #[test]
+    fn crlf_files_keep_their_line_endings() {
+        let content = CONFIG.replace('\n', "\r\n");
+        let mut document = Document::parse(&content);
+        assert_eq!(document.to_string(), content);
+        assert_eq!(document.get("Settings", "MarqueeHeight"), Some("360"));
+        document.set("Output:Top", "Screen", "2");
+        document.set("Commands", "game-start", "echo");
+        let expected = content.replace("Screen = 1", "Screen = 2") + "\r\n[Commands]\r\ngame-start = echo\r\n";
+        assert_eq!(document.to_string(), expected);
+    }
+
+    #[test]
+    fn a_missing_trailing_newline_stays_missing() {
+        let mut document = Document::parse("[Settings]\nA = 1");
+        document.set("Settings", "B", "2");
+        assert_eq!(document.to_string(), "[Settings]\nA = 1\nB = 2");
+    }
+}
@@ -0,0 +1,15 @@
+use log::error;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+/// Writes a single input command line to the mpv IPC pipe.
+pub fn send_command(ipc_channel: &str, command: &str) {
+    match OpenOptions::new().write(true).open(ipc_channel) {
+        Ok(mut pipe) => {
+            if let Err(e) = pipe.write_all(format!("{}\n", command).as_bytes()) {
+                error!("Failed to write to MPV pipe {}: {}", ipc_channel, e);
+            }
+        }
+        Err(e) => error!("Failed to open MPV pipe at {}: {}", ipc_channel, e),
+    }
+}
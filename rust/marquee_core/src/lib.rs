@@ -0,0 +1,12 @@
+//! Building blocks shared by the Marquee Manager tools and usable by third-party tools:
+//! locating, resolving and editing the configuration, the EmulationStation system registry
+//! and settings, `{placeholder}` templates, localized messages and the mpv IPC pipe.
+
+pub mod config;
+pub mod es_settings;
+pub mod i18n;
+pub mod ini;
+pub mod ipc;
+pub mod registry;
+pub mod systems;
+pub mod template;
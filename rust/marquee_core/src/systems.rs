@@ -0,0 +1,74 @@
+//! The systems EmulationStation knows, read from its es_systems*.cfg files.
+
+use quick_xml::de::from_str;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use glob::glob;
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct System {
+    name: String,
+    path: String,
+    theme: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct SystemList {
+    #[serde(rename = "system", default)]
+    systems: Vec<System>,
+}
+
+/// Maps every system name to the name of its rom folder, read from the es_systems*.cfg
+/// files in `config_directory`.
+pub fn load_all_systems_configs(config_directory: &Path) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let mut all_system_folders = HashMap::new();
+    let pattern = config_directory.join("es_systems*.cfg");
+
+    for entry in glob(pattern.to_str().ok_or("non-UTF-8 systems path")?)? {
+        let path = entry?;
+        let xml_content = fs::read_to_string(path)?;
+        let system_list: SystemList = from_str(&xml_content)?;
+
+        for system in system_list.systems {
+            // Replicate Python's os.path.basename logic
+            let system_path = PathBuf::from(system.path.strip_prefix("~/").unwrap_or(&system.path));
+            if let Some(folder_name) = system_path.file_name().and_then(|s| s.to_str()) {
+                all_system_folders.insert(system.name.clone(), folder_name.to_string());
+            }
+        }
+    }
+
+    Ok(all_system_folders)
+}
+
+/// The rom folder of `system_name`, the name itself for systems the es_systems files omit.
+pub fn folder<'a>(systems: &'a HashMap<String, String>, system_name: &'a str) -> &'a str {
+    systems.get(system_name).map(String::as_str).unwrap_or(system_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folder_defaults_to_the_system_name() {
+        let systems = HashMap::from([("megadrive".to_string(), "genesis".to_string())]);
+        assert_eq!(folder(&systems, "megadrive"), "genesis");
+        assert_eq!(folder(&systems, "snes"), "snes");
+    }
+
+    #[test]
+    fn missing_folders_have_no_systems() {
+        assert!(load_all_systems_configs(Path::new("missing")).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_are_errors() {
+        use std::os::unix::ffi::OsStrExt;
+        let directory = Path::new(std::ffi::OsStr::from_bytes(b"systems\xff"));
+        assert!(load_all_systems_configs(directory).is_err());
+    }
+}
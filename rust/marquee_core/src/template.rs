@@ -0,0 +1,355 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How substituted values are escaped before being spliced into the output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Escape {
+    /// Values are inserted as-is (file name templates).
+    #[default]
+    Raw,
+    /// Values are escaped for a `cmd /C` command line.
+    Shell,
+    /// Values are escaped for the command line of a program started without a shell. Values
+    /// outside double quotes get quotes of their own when they contain whitespace.
+    Args,
+    /// Values are escaped for an mpv input command written to the IPC pipe.
+    Ipc,
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    UnknownPlaceholder { key: String, name: String, known: Vec<String> },
+    MissingValue { key: String, name: String },
+    NonUnicodePath { key: String, name: String, path: PathBuf },
+    Unquotable { key: String, name: String },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder { key, name, known } => write!(
+                f,
+                "Unknown placeholder {{{}}} in {} (known placeholders: {})",
+                name,
+                key,
+                known.iter().map(|k| format!("{{{}}}", k)).collect::<Vec<_>>().join(", ")
+            ),
+            TemplateError::MissingValue { key, name } => {
+                write!(f, "No value available for placeholder {{{}}} in {}", name, key)
+            }
+            TemplateError::NonUnicodePath { key, name, path } => write!(
+                f,
+                "Path {:?} for placeholder {{{}}} in {} is not valid Unicode",
+                path, name, key
+            ),
+            TemplateError::Unquotable { key, name } => write!(
+                f,
+                "Value for placeholder {{{}}} in {} contains a double quote and cannot be quoted for the command line",
+                name, key
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// A value substituted into a template. Paths are kept as paths until render time so that
+/// non-Unicode paths are reported instead of silently becoming empty strings.
+pub enum Value<'a> {
+    Text(Cow<'a, str>),
+    Path(&'a Path),
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(s: &'a str) -> Self {
+        Value::Text(Cow::Borrowed(s))
+    }
+}
+
+impl<'a> From<&'a String> for Value<'a> {
+    fn from(s: &'a String) -> Self {
+        Value::Text(Cow::Borrowed(s.as_str()))
+    }
+}
+
+impl From<String> for Value<'_> {
+    fn from(s: String) -> Self {
+        Value::Text(Cow::Owned(s))
+    }
+}
+
+impl From<i32> for Value<'_> {
+    fn from(n: i32) -> Self {
+        Value::Text(Cow::Owned(n.to_string()))
+    }
+}
+
+impl<'a> From<&'a Path> for Value<'a> {
+    fn from(p: &'a Path) -> Self {
+        Value::Path(p)
+    }
+}
+
+impl<'a> From<&'a PathBuf> for Value<'a> {
+    fn from(p: &'a PathBuf) -> Self {
+        Value::Path(p.as_path())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    // `quoted` records whether the placeholder sits inside a double-quoted span of the template
+    Placeholder { name: String, quoted: bool },
+}
+
+/// A parsed `{placeholder}` template, validated against the placeholders its key allows.
+#[derive(Debug, Clone, Default)]
+pub struct Template {
+    key: String,
+    escape: Escape,
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(key: &str, source: &str, known: &[&str], escape: Escape) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut quoted = false;
+        let mut rest = source;
+
+        while let Some(c) = rest.chars().next() {
+            if c == '{' {
+                // Only `{identifier}` is a placeholder; anything else (e.g. JSON) stays literal
+                if let Some(end) = rest.find('}') {
+                    let name = &rest[1..end];
+                    if is_identifier(name) {
+                        if !known.contains(&name) {
+                            return Err(TemplateError::UnknownPlaceholder {
+                                key: key.to_string(),
+                                name: name.to_string(),
+                                known: known.iter().map(|k| k.to_string()).collect(),
+                            });
+                        }
+                        if !literal.is_empty() {
+                            parts.push(Part::Literal(std::mem::take(&mut literal)));
+                        }
+                        parts.push(Part::Placeholder { name: name.to_string(), quoted });
+                        rest = &rest[end + 1..];
+                        continue;
+                    }
+                }
+            } else if c == '"' {
+                quoted = !quoted;
+            }
+            literal.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Template { key: key.to_string(), escape, parts })
+    }
+
+    /// Names of the placeholders referenced by this template, in order of appearance.
+    pub fn placeholders(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Placeholder { name, .. } => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    pub fn render(&self, values: &[(&str, Value)]) -> Result<String, TemplateError> {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => output.push_str(text),
+                Part::Placeholder { name, quoted } => {
+                    let value = values
+                        .iter()
+                        .find(|(n, _)| n == name)
+                        .map(|(_, v)| v)
+                        .ok_or_else(|| TemplateError::MissingValue {
+                            key: self.key.clone(),
+                            name: name.clone(),
+                        })?;
+                    let text: &str = match value {
+                        Value::Text(text) => text,
+                        Value::Path(path) => path.to_str().ok_or_else(|| TemplateError::NonUnicodePath {
+                            key: self.key.clone(),
+                            name: name.clone(),
+                            path: path.to_path_buf(),
+                        })?,
+                    };
+                    match self.escape {
+                        Escape::Raw => output.push_str(text),
+                        Escape::Ipc => output.push_str(&escape_ipc(text, *quoted)),
+                        Escape::Shell if *quoted && text.contains('"') => {
+                            return Err(TemplateError::Unquotable {
+                                key: self.key.clone(),
+                                name: name.clone(),
+                            });
+                        }
+                        Escape::Args if text.contains('"') => {
+                            return Err(TemplateError::Unquotable {
+                                key: self.key.clone(),
+                                name: name.clone(),
+                            });
+                        }
+                        Escape::Shell => output.push_str(&escape_shell(text, *quoted)),
+                        Escape::Args => output.push_str(&escape_args(text, *quoted)),
+                    }
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// cmd.exe expands %VAR% even inside quotes, so a quoted `%` is emitted as `"^%"` (close the
+// quote, caret-escape, reopen). Outside quotes every metacharacter is caret-escaped.
+fn escape_shell(value: &str, quoted: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' if quoted => escaped.push_str("\"^%\""),
+            '^' | '&' | '|' | '<' | '>' | '(' | ')' | '%' | '!' | '"' if !quoted => {
+                escaped.push('^');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Programs split their command line with the C runtime rules, where backslashes are literal
+// except before a quote: the ones ending a quoted value are doubled so the closing quote stays.
+// An unquoted value with whitespace would become several arguments, so it is quoted here
+fn escape_args(value: &str, quoted: bool) -> String {
+    if !quoted && !value.is_empty() && !value.contains(char::is_whitespace) {
+        return value.to_string();
+    }
+    let trailing = value.len() - value.trim_end_matches('\\').len();
+    let escaped = format!("{}{}", value, "\\".repeat(trailing));
+    if quoted {
+        escaped
+    } else {
+        format!("\"{}\"", escaped)
+    }
+}
+
+// mpv's input command parser (and its JSON IPC) unescape backslashes and quotes inside
+// double-quoted arguments; unquoted arguments end at whitespace, so those are left alone.
+fn escape_ipc(value: &str, quoted: bool) -> String {
+    if !quoted {
+        return value.to_string();
+    }
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: &str = r"Tom & Jerry 100% Fun!.zip";
+
+    fn render(source: &str, escape: Escape, value: &str) -> Result<String, TemplateError> {
+        Template::parse("Test", source, &["value"], escape)?.render(&[("value", Value::from(value))])
+    }
+
+    #[test]
+    fn raw_inserts_values_as_they_are() {
+        assert_eq!(render("roms\\{value}", Escape::Raw, ROM).unwrap(), r"roms\Tom & Jerry 100% Fun!.zip");
+        assert_eq!(render("\"{value}\"", Escape::Raw, "a\"b\\").unwrap(), "\"a\"b\\\"");
+    }
+
+    #[test]
+    fn shell_escapes_metacharacters_outside_quotes() {
+        assert_eq!(render("echo {value}", Escape::Shell, ROM).unwrap(), "echo Tom ^& Jerry 100^% Fun^!.zip");
+        assert_eq!(render("echo {value}", Escape::Shell, "say \"hi\"").unwrap(), "echo say ^\"hi^\"");
+        assert_eq!(render("echo {value}", Escape::Shell, r"C:\roms\").unwrap(), r"echo C:\roms\");
+    }
+
+    #[test]
+    fn shell_breaks_out_of_quotes_for_percent_signs_only() {
+        assert_eq!(render("echo \"{value}\"", Escape::Shell, ROM).unwrap(), "echo \"Tom & Jerry 100\"^%\" Fun!.zip\"");
+        assert_eq!(render("\"{value}\"", Escape::Shell, r"C:\My Roms\").unwrap(), r#""C:\My Roms\""#);
+    }
+
+    #[test]
+    fn shell_rejects_double_quotes_inside_quotes() {
+        let error = render("echo \"{value}\"", Escape::Shell, "say \"hi\"").unwrap_err();
+        assert!(matches!(error, TemplateError::Unquotable { ref name, .. } if name == "value"));
+    }
+
+    #[test]
+    fn args_doubles_trailing_backslashes_of_quoted_values() {
+        assert_eq!(render("\"{value}\"", Escape::Args, r"C:\My Roms\").unwrap(), r#""C:\My Roms\\""#);
+        assert_eq!(render("\"{value}\"", Escape::Args, ROM).unwrap(), "\"Tom & Jerry 100% Fun!.zip\"");
+    }
+
+    #[test]
+    fn args_quotes_unquoted_values_with_whitespace() {
+        assert_eq!(render("--image={value}", Escape::Args, ROM).unwrap(), "--image=\"Tom & Jerry 100% Fun!.zip\"");
+        assert_eq!(render("{value}", Escape::Args, r"C:\My Roms\").unwrap(), r#""C:\My Roms\\""#);
+        assert_eq!(render("--screen={value}", Escape::Args, "2").unwrap(), "--screen=2");
+        assert_eq!(render("--title={value}", Escape::Args, "").unwrap(), "--title=\"\"");
+    }
+
+    #[test]
+    fn args_rejects_double_quotes() {
+        assert!(matches!(render("\"{value}\"", Escape::Args, "a\"b"), Err(TemplateError::Unquotable { .. })));
+        assert!(matches!(render("{value}", Escape::Args, "a\"b"), Err(TemplateError::Unquotable { .. })));
+    }
+
+    #[test]
+    fn ipc_escapes_quoted_values() {
+        assert_eq!(render("loadfile \"{value}\"", Escape::Ipc, r#"C:\roms\say "hi".png"#).unwrap(), r#"loadfile "C:\\roms\\say \"hi\".png""#);
+        assert_eq!(render("show-text \"{value}\"", Escape::Ipc, "a\nb").unwrap(), "show-text \"a\\nb\"");
+        assert_eq!(render("show-text {value}", Escape::Ipc, ROM).unwrap(), "show-text Tom & Jerry 100% Fun!.zip");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_rejected_with_the_known_ones() {
+        let error = Template::parse("MPVKillCommand", "echo {IPCChanel}", &["IPCChannel"], Escape::Shell).unwrap_err();
+        match error {
+            TemplateError::UnknownPlaceholder { key, name, known } => {
+                assert_eq!((key.as_str(), name.as_str()), ("MPVKillCommand", "IPCChanel"));
+                assert_eq!(known, vec!["IPCChannel".to_string()]);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn braces_that_are_not_placeholders_stay_literal() {
+        let source = r#"{"command": ["loadfile", "{value}"]}"#;
+        let template = Template::parse("Test", source, &["value"], Escape::Ipc).unwrap();
+        assert_eq!(template.placeholders().collect::<Vec<_>>(), vec!["value"]);
+        assert_eq!(template.render(&[("value", Value::from("a.png"))]).unwrap(), r#"{"command": ["loadfile", "a.png"]}"#);
+    }
+
+    #[test]
+    fn missing_values_are_reported() {
+        let template = Template::parse("Test", "{value}", &["value"], Escape::Raw).unwrap();
+        assert!(matches!(template.render(&[]), Err(TemplateError::MissingValue { .. })));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::cache;
+use crate::config::Config;
+use crate::gamelist;
+use crate::generator;
+use crate::layouts::LayoutStore;
+use crate::marquee;
+use log::{error, info};
+use marquee_core::systems;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// What happened to each game during a batch run.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub generated: usize,
+    pub skipped: usize,
+    pub failed: Vec<String>,
+}
+
+enum Outcome {
+    Generated,
+    Skipped,
+    Failed(String),
+}
+
+/// Generates the marquee of every game whose design images exist but has no real marquee, using
+/// `jobs` worker threads. Marquees already cached for their current artwork and layout are kept
+/// unless `force` is set.
+pub fn generate_all(
+    config: &Config,
+    systems: &HashMap<String, String>,
+    layouts: &LayoutStore,
+    jobs: usize,
+    force: bool,
+) -> Summary {
+    let games = list_games(config, systems);
+    let total = games.len();
+    let messages = &config.messages;
+    println!("{}", messages.format("batch_found", &[("games", &total), ("systems", &systems.len())]));
+
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let summary = Mutex::new(Summary::default());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some((system_name, game_name)) = games.get(index) else {
+                    break;
+                };
+                let outcome = generate_game(system_name, game_name, config, systems, layouts, force);
+                let count = done.fetch_add(1, Ordering::SeqCst) + 1;
+
+                let mut summary = summary.lock().unwrap();
+                let label = match outcome {
+                    Outcome::Generated => {
+                        summary.generated += 1;
+                        messages.get("batch_generated").to_string()
+                    }
+                    Outcome::Skipped => {
+                        summary.skipped += 1;
+                        messages.get("batch_skipped").to_string()
+                    }
+                    Outcome::Failed(reason) => {
+                        summary.failed.push(format!("{}/{}: {}", system_name, game_name, reason));
+                        messages.format("batch_failed", &[("reason", &reason)])
+                    }
+                };
+                println!("[{}/{}] {}/{} {}", count, total, system_name, game_name, label);
+            });
+        }
+    });
+
+    // Stores only scan the cache now and then, make sure the batch leaves it within its size
+    cache::evict(config);
+
+    let summary = summary.into_inner().unwrap();
+    info!(
+        "Batch generation done: {} generated, {} skipped, {} failed",
+        summary.generated,
+        summary.skipped,
+        summary.failed.len()
+    );
+    summary
+}
+
+fn generate_game(
+    system_name: &str,
+    game_name: &str,
+    config: &Config,
+    systems: &HashMap<String, String>,
+    layouts: &LayoutStore,
+    force: bool,
+) -> Outcome {
+    let config = config.for_system(system_name);
+    if marquee::find_real_game_marquee(system_name, game_name, config, systems).is_some() {
+        return Outcome::Skipped;
+    }
+    let layout = layouts.get(system_name, game_name, &config.layout);
+    let system_folder = systems::folder(systems, system_name);
+    // The cache path is a hash of the sources and layout, so an existing file is up to date
+    let Some(target_path) = generator::generated_marquee_path(system_name, system_folder, game_name, &layout, config) else {
+        return Outcome::Skipped;
+    };
+    if !force && target_path.exists() {
+        return Outcome::Skipped;
+    }
+
+    match generator::write_marquee(system_name, system_folder, game_name, &target_path, &layout, config) {
+        Ok(()) => Outcome::Generated,
+        Err(e) => {
+            error!("Failed to generate marquee {:?}: {}", target_path, e);
+            Outcome::Failed(e.to_string())
+        }
+    }
+}
+
+// Every game listed in the systems' gamelist.xml, sorted for a stable progress order
+fn list_games(config: &Config, systems: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut games = Vec::new();
+    for (system_name, system_folder) in systems {
+        let gamelist_path = config.for_system(system_name).settings.roms_path.join(system_folder).join("gamelist.xml");
+        if !gamelist_path.exists() {
+            continue;
+        }
+        match gamelist::load_gamelist(&gamelist_path) {
+            Ok(entries) => games.extend(
+                entries
+                    .iter()
+                    .filter_map(|game| game.rom_stem())
+                    .map(|game_name| (system_name.clone(), game_name.to_string())),
+            ),
+            Err(e) => error!("Failed to read {:?}: {}", gamelist_path, e),
+        }
+    }
+    games.sort();
+    games.dedup();
+    games
+}
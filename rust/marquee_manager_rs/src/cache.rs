@@ -0,0 +1,198 @@
+use crate::config::Config;
+use log::{error, info};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Hex digits of the hash kept in cache file names
+const KEY_LENGTH: usize = 32;
+
+// The cache is scanned for eviction once this fraction of CacheMaxSize has been written
+const EVICT_THRESHOLD_DIVISOR: u64 = 10;
+
+// Bytes stored since the last eviction scan
+static WRITTEN: AtomicU64 = AtomicU64::new(0);
+// Held during a scan, so concurrent stores don't delete the same files
+static EVICTION: Mutex<()> = Mutex::new(());
+
+/// Builds the hash a cached file is named after: any change to the hashed source files or
+/// parameters gives a new name, so stale entries are never served.
+pub struct CacheKey {
+    hasher: Sha256,
+}
+
+impl CacheKey {
+    pub fn new() -> Self {
+        CacheKey { hasher: Sha256::new() }
+    }
+
+    pub fn text(&mut self, value: &str) -> &mut Self {
+        // Length-prefixed so ("ab", "c") and ("a", "bc") differ
+        self.hasher.update((value.len() as u64).to_le_bytes());
+        self.hasher.update(value.as_bytes());
+        self
+    }
+
+    pub fn number(&mut self, value: i64) -> &mut Self {
+        self.hasher.update(value.to_le_bytes());
+        self
+    }
+
+    /// Hashes the path, size and modification date of a file, which is enough to notice it was
+    /// replaced without reading it.
+    pub fn file(&mut self, path: &Path) -> io::Result<&mut Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.text(&path.to_string_lossy())
+            .number(metadata.len() as i64)
+            .number(modified.as_nanos() as i64);
+        Ok(self)
+    }
+
+    /// Hashes the marquee geometry every cached marquee is rendered for.
+    pub fn geometry(&mut self, config: &Config) -> &mut Self {
+        self.number(config.settings.marquee_width as i64)
+            .number(config.settings.marquee_height as i64)
+            .number(config.settings.marquee_border as i64)
+    }
+
+    /// `<directory>/<stem>-<hash>.<extension>`
+    pub fn path(&self, directory: &Path, stem: &str, extension: &str) -> PathBuf {
+        let hash: String = self.hasher.clone().finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        directory.join(format!("{}-{}.{}", stem, &hash[..KEY_LENGTH], extension))
+    }
+}
+
+/// Returns `path` if it is cached, marking it as recently used.
+pub fn lookup(path: &Path) -> Option<PathBuf> {
+    if !path.exists() {
+        return None;
+    }
+    // The modification date doubles as the last use date for eviction
+    if let Err(e) = File::options().write(true).open(path).and_then(|f| f.set_modified(SystemTime::now())) {
+        error!("Failed to touch cached file {:?}: {}", path, e);
+    }
+    Some(path.to_path_buf())
+}
+
+/// Every directory holding cached marquees, those set by `[System:<name>]` overrides included.
+pub fn directories(config: &Config) -> Vec<&Path> {
+    let mut directories = Vec::new();
+    for config in std::iter::once(config).chain(config.system_configs.values().map(|c| c.as_ref())) {
+        let settings = &config.settings;
+        for directory in [
+            &settings.generated_cache_path,
+            &settings.marquee_convert_cache_path,
+            &settings.svg_cache_path,
+            &settings.marquee_text_fallback_cache_path,
+        ] {
+            if !directories.contains(&directory.as_path()) {
+                directories.push(directory.as_path());
+            }
+        }
+    }
+    directories
+}
+
+/// Whether `path` is named like a cache entry, `<stem>-<hash>.<extension>`. Only such files are
+/// evicted or purged, in case a cache folder also holds other files.
+fn is_entry(path: &Path) -> bool {
+    let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        return false;
+    };
+    stem.rsplit_once('-').is_some_and(|(_, hash)| {
+        hash.len() == KEY_LENGTH && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    })
+}
+
+// The cache files of every cache directory
+fn entries(config: &Config) -> impl Iterator<Item = fs::DirEntry> + '_ {
+    directories(config)
+        .into_iter()
+        .filter_map(|directory| fs::read_dir(directory).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()) && is_entry(&entry.path()))
+}
+
+/// Saves `image` to `path`, see [`store_with`].
+pub fn store(image: &image::RgbaImage, path: &Path, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    store_with(path, config, |path| Ok(image.save(path)?))
+}
+
+/// Creates the directory of `path` and lets `write` fill it. Once enough has been written since
+/// the last scan, the least recently used entries are evicted.
+pub fn store_with(
+    path: &Path,
+    config: &Config,
+    write: impl FnOnce(&Path) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write(path)?;
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let threshold = max_bytes(config) / EVICT_THRESHOLD_DIVISOR;
+    if WRITTEN.fetch_add(size, Ordering::SeqCst) + size >= threshold {
+        evict(config);
+    }
+    Ok(())
+}
+
+// CacheMaxSize in bytes, a size too large to count meaning no limit in practice
+fn max_bytes(config: &Config) -> u64 {
+    config.settings.cache_max_size.saturating_mul(1024 * 1024)
+}
+
+/// Deletes the least recently used files until the cache fits in `CacheMaxSize` megabytes.
+pub fn evict(config: &Config) {
+    let max_bytes = max_bytes(config);
+    if max_bytes == 0 {
+        return;
+    }
+    // Another thread is already scanning, and will count what was just written
+    let Ok(_scan) = EVICTION.try_lock() else {
+        return;
+    };
+    WRITTEN.store(0, Ordering::SeqCst);
+    let mut entries: Vec<(SystemTime, u64, PathBuf)> = entries(config)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+        })
+        .collect();
+
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    if total <= max_bytes {
+        return;
+    }
+    entries.sort();
+    for (_, size, path) in entries {
+        if total <= max_bytes {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                info!("Evicted {:?} from the marquee cache", path);
+                total -= size;
+            }
+            Err(e) => error!("Failed to evict {:?}: {}", path, e),
+        }
+    }
+}
+
+/// Deletes every cached marquee, returning the number of files removed.
+pub fn purge(config: &Config) -> usize {
+    let mut removed = 0;
+    for entry in entries(config) {
+        match fs::remove_file(entry.path()) {
+            Ok(()) => removed += 1,
+            Err(e) => error!("Failed to remove {:?}: {}", entry.path(), e),
+        }
+    }
+    info!("Purged {} cached marquees", removed);
+    removed
+}
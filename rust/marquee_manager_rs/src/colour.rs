@@ -0,0 +1,23 @@
+use image::Rgba;
+
+/// Parses `#rrggbb`, `#rrggbbaa`, `transparent` or one of a few colour names.
+pub fn parse_colour(value: &str) -> Option<Rgba<u8>> {
+    let value = value.trim().to_lowercase();
+    match value.as_str() {
+        "transparent" => return Some(Rgba([0, 0, 0, 0])),
+        "black" => return Some(Rgba([0, 0, 0, 255])),
+        "white" => return Some(Rgba([255, 255, 255, 255])),
+        _ => {}
+    }
+
+    let hex = value.strip_prefix('#')?;
+    if !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    match hex.len() {
+        6 => Some(Rgba([channel(0)?, channel(2)?, channel(4)?, 255])),
+        8 => Some(Rgba([channel(0)?, channel(2)?, channel(4)?, channel(6)?])),
+        _ => None,
+    }
+}
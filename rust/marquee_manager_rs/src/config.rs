@@ -1,105 +1,576 @@
-use serde::{de::{self, Deserializer}, Deserialize};
+use crate::colour::parse_colour;
+use crate::convert::Fill;
+use crate::designer::Designs;
+use crate::generator::Layout;
+use crate::media::AcceptedFormats;
+use crate::messages;
+use crate::outputs::{self, Output};
+use crate::overlay::Overlay;
+use crate::profiles::Profiles;
+use crate::template::Templates;
+use log::info;
+use marquee_core::i18n::Catalog;
+use marquee_core::config::{deserialize_bool_from_string, find_retrobat_path, plugin_path, resolve_path};
+use marquee_core::es_settings::{self, EsSettings};
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::fs;
 
-// Custom deserializer for boolean values that might be strings
-fn deserialize_bool_from_string<'de, D>(deserializer: D) -> Result<bool, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: String = String::deserialize(deserializer)?;
-    match s.to_lowercase().as_str() {
-        "true" => Ok(true),
-        "false" => Ok(false),
-        _ => Err(de::Error::unknown_variant(&s, &["true", "false"])),
+fn default_marquee_width() -> i32 {
+    1920
+}
+
+fn default_marquee_height() -> i32 {
+    360
+}
+
+fn default_marquee_border() -> i32 {
+    30
+}
+
+fn default_accepted_formats() -> String {
+    "mp4,gif,png,jpg,svg".to_string()
+}
+
+fn default_roms_path() -> PathBuf {
+    PathBuf::from("{RetroBatPath}").join("roms")
+}
+
+fn default_default_image_path() -> PathBuf {
+    PathBuf::from("{PluginPath}").join("images").join("default.png")
+}
+
+fn default_marquee_image_path() -> PathBuf {
+    PathBuf::from("{PluginPath}").join("images")
+}
+
+fn default_marquee_file_path() -> String {
+    "{system_name}-{game_name}".to_string()
+}
+
+fn default_marquee_file_path_default() -> String {
+    "{system_name}/images/{game_name}-marquee".to_string()
+}
+
+// The logos of the frontend's theme
+fn default_logos_path() -> PathBuf {
+    ["emulationstation", ".emulationstation", "themes", "{Theme}", "art", "logos"]
+        .iter()
+        .fold(PathBuf::from("{RetroBatPath}"), |path, part| path.join(part))
+}
+
+fn default_system_file_path() -> String {
+    "{system_name}".to_string()
+}
+
+fn default_collection_file_path() -> String {
+    "auto-{collection_name}".to_string()
+}
+
+fn default_collection_alternativ_names() -> String {
+    "custom-, arcade".to_string()
+}
+
+fn default_collection_correlation() -> String {
+    "recent:lastplayed, all:allgames, 2players:at2players, 4players:at4players, collections:custom-collections".to_string()
+}
+
+fn default_ipc_channel() -> String {
+    marquee_core::config::DEFAULT_IPC_CHANNEL.to_string()
+}
+
+fn default_screen_number() -> i32 {
+    2
+}
+
+fn default_mpv_path() -> PathBuf {
+    PathBuf::from("{PluginPath}").join("mpv").join("mpv.exe")
+}
+
+fn default_mpv_launch_command() -> String {
+    "\"{MPVPath}\" --input-ipc-server={IPCChannel} --screen={ScreenNumber} --no-border --ontop --autofit-larger=100%x100% \"{DefaultImagePath}\"".to_string()
+}
+
+// Where the es_systems*.cfg files of RetroBat's EmulationStation are
+fn default_es_systems_path() -> PathBuf {
+    PathBuf::from("{RetroBatPath}").join("emulationstation").join(".emulationstation")
+}
+
+fn default_es_event_file_path() -> PathBuf {
+    PathBuf::from("ESEvent.arg")
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_port() -> i32 {
+    8080
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_marquee_auto_convert_fill() -> String {
+    "blur".to_string()
+}
+
+fn default_marquee_convert_cache_path() -> PathBuf {
+    PathBuf::from("cache").join("converted")
+}
+
+fn default_marquee_text_fallback_cache_path() -> PathBuf {
+    PathBuf::from("cache").join("fallback")
+}
+
+fn default_generated_cache_path() -> PathBuf {
+    PathBuf::from("cache").join("generated")
+}
+
+fn default_cache_max_size() -> u64 {
+    500
+}
+
+fn default_svg_background() -> String {
+    "#000000".to_string()
+}
+
+fn default_svg_cache_path() -> PathBuf {
+    PathBuf::from("cache").join("svg")
+}
+
+fn default_generator_templates_path() -> PathBuf {
+    PathBuf::from("templates")
+}
+
+fn default_generator_encoder_command() -> String {
+    "ffmpeg -y -loglevel error -framerate {frame_rate} -i \"{frames}\" -c:v libx264 -pix_fmt yuv420p \"{output}\"".to_string()
+}
+
+fn default_generator_layouts_path() -> PathBuf {
+    PathBuf::from("generated_layouts.json")
+}
+
+fn default_overlay_template() -> String {
+    "{name} — {year} — {developer} — {players}".to_string()
+}
+
+fn default_overlay_separator() -> String {
+    "\" — \"".to_string()
+}
+
+fn default_overlay_position() -> String {
+    "bottom-center".to_string()
+}
+
+fn default_overlay_font_size() -> i32 {
+    40
+}
+
+fn default_overlay_duration() -> i32 {
+    5
+}
+
+/// Every key of the [Settings] section, as written in config.ini: the field names serde
+/// deserializes `Settings` with, so the list follows the struct.
+pub fn settings_keys() -> &'static [&'static str] {
+    let mut capture = FieldCapture::default();
+    let _ = Settings::deserialize(&mut capture);
+    capture.fields
+}
+
+// A deserializer that only records the field names a struct asks for, then gives up
+#[derive(Default)]
+struct FieldCapture {
+    fields: &'static [&'static str],
+}
+
+impl<'de> Deserializer<'de> for &mut FieldCapture {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs are supported"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.fields = fields;
+        Err(de::Error::custom("field names captured"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// The value a missing [Settings] key takes, as it would be written in config.ini. None for
+/// keys without a default. Read from a [Settings] section without keys, so it is whatever the
+/// `default` of the field gives.
+pub fn default_setting(key: &str) -> Option<String> {
+    let defaults: SettingsSection = serde_ini::from_str("[Settings]\n").ok()?;
+    match serde_json::to_value(defaults.settings).ok()?.get(key)? {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Settings {
-    #[serde(rename = "Language")]
-    pub language: String,
-    #[serde(rename = "MarqueeWidth")]
+    /// Taken from the frontend settings when config.ini has none.
+    #[serde(rename = "Language", default)]
+    pub language: Option<String>,
+    #[serde(rename = "MarqueeWidth", default = "default_marquee_width")]
     pub marquee_width: i32,
-    #[serde(rename = "MarqueeHeight")]
+    #[serde(rename = "MarqueeHeight", default = "default_marquee_height")]
     pub marquee_height: i32,
-    #[serde(rename = "MarqueeBorder")]
+    #[serde(rename = "MarqueeBorder", default = "default_marquee_border")]
     pub marquee_border: i32,
-    #[serde(rename = "MarqueeAutoConvert", deserialize_with = "deserialize_bool_from_string")]
+    #[serde(rename = "MarqueeAutoConvert", default, deserialize_with = "deserialize_bool_from_string")]
     pub marquee_auto_convert: bool,
-    #[serde(rename = "AcceptedFormats")]
+    #[serde(rename = "MarqueeAutoConvertFill", default = "default_marquee_auto_convert_fill")]
+    pub marquee_auto_convert_fill: String,
+    #[serde(rename = "MarqueeConvertCachePath", default = "default_marquee_convert_cache_path")]
+    pub marquee_convert_cache_path: PathBuf,
+    #[serde(rename = "MarqueeTextFallback", default, deserialize_with = "deserialize_bool_from_string")]
+    pub marquee_text_fallback: bool,
+    #[serde(rename = "MarqueeTextFallbackCachePath", default = "default_marquee_text_fallback_cache_path")]
+    pub marquee_text_fallback_cache_path: PathBuf,
+    #[serde(rename = "GeneratedCachePath", default = "default_generated_cache_path")]
+    pub generated_cache_path: PathBuf,
+    #[serde(rename = "CacheMaxSize", default = "default_cache_max_size")]
+    pub cache_max_size: u64,
+    #[serde(rename = "SvgBackground", default = "default_svg_background")]
+    pub svg_background: String,
+    #[serde(rename = "SvgCachePath", default = "default_svg_cache_path")]
+    pub svg_cache_path: PathBuf,
+    #[serde(rename = "AcceptedFormats", default = "default_accepted_formats")]
     pub accepted_formats: String,
     #[serde(rename = "RetroBatPath")]
     pub retrobat_path: Option<PathBuf>,
-    #[serde(rename = "RomsPath")]
+    #[serde(rename = "RomsPath", default = "default_roms_path")]
     pub roms_path: PathBuf,
-    #[serde(rename = "DefaultImagePath")]
+    #[serde(rename = "DefaultImagePath", default = "default_default_image_path")]
     pub default_image_path: PathBuf,
-    #[serde(rename = "MarqueeImagePath")]
+    #[serde(rename = "MarqueeImagePath", default = "default_marquee_image_path")]
     pub marquee_image_path: PathBuf,
-    #[serde(rename = "MarqueeFilePath")]
+    #[serde(rename = "MarqueeFilePath", default = "default_marquee_file_path")]
     pub marquee_file_path: String,
-    #[serde(rename = "MarqueeImagePathDefault")]
+    #[serde(rename = "MarqueeImagePathDefault", default = "default_roms_path")]
     pub marquee_image_path_default: PathBuf,
-    #[serde(rename = "MarqueeFilePathDefault")]
+    #[serde(rename = "MarqueeFilePathDefault", default = "default_marquee_file_path_default")]
     pub marquee_file_path_default: String,
-    #[serde(rename = "MarqueeAutoScraping", deserialize_with = "deserialize_bool_from_string")]
+    #[serde(rename = "MarqueeAutoScraping", default, deserialize_with = "deserialize_bool_from_string")]
     pub marquee_auto_scraping: bool,
-    #[serde(rename = "MarqueeAutoScrapingDebug", deserialize_with = "deserialize_bool_from_string")]
+    #[serde(rename = "MarqueeAutoScrapingDebug", default, deserialize_with = "deserialize_bool_from_string")]
     pub marquee_auto_scraping_debug: bool,
-    #[serde(rename = "SystemMarqueePath")]
+    #[serde(rename = "SystemMarqueePath", default = "default_logos_path")]
     pub system_marquee_path: PathBuf,
-    #[serde(rename = "SystemFilePath")]
+    #[serde(rename = "SystemFilePath", default = "default_system_file_path")]
     pub system_file_path: String,
-    #[serde(rename = "CollectionMarqueePath")]
+    #[serde(rename = "CollectionMarqueePath", default = "default_logos_path")]
     pub collection_marquee_path: PathBuf,
-    #[serde(rename = "CollectionFilePath")]
+    #[serde(rename = "CollectionFilePath", default = "default_collection_file_path")]
     pub collection_file_path: String,
-    #[serde(rename = "CollectionAlternativNames")]
+    #[serde(rename = "CollectionAlternativNames", default = "default_collection_alternativ_names")]
     pub collection_alternativ_names: String,
-    #[serde(rename = "CollectionCorrelation")]
+    #[serde(rename = "CollectionCorrelation", default = "default_collection_correlation")]
     pub collection_correlation: String,
-    #[serde(rename = "IPCChannel")]
+    #[serde(rename = "IPCChannel", default = "default_ipc_channel")]
     pub ipc_channel: String,
-    #[serde(rename = "ScreenNumber")]
+    #[serde(rename = "ScreenNumber", default = "default_screen_number")]
     pub screen_number: i32,
-    #[serde(rename = "MPVPath")]
+    #[serde(rename = "MPVPath", default = "default_mpv_path")]
     pub mpv_path: PathBuf,
-    #[serde(rename = "MPVLaunchCommand")]
+    #[serde(rename = "MPVLaunchCommand", default = "default_mpv_launch_command")]
     pub mpv_launch_command: String,
-    #[serde(rename = "MPVKillCommand")]
-    pub mpv_kill_command: String,
-    #[serde(rename = "MPVTestCommand")]
-    pub mpv_test_command: String,
-    #[serde(rename = "IMPath")]
-    pub im_path: PathBuf,
-    #[serde(rename = "IMConvertCommand")]
-    pub im_convert_command: String,
+    /// Run once at startup, to close media players left by a previous run.
+    #[serde(rename = "MPVKillCommand", default)]
+    pub mpv_kill_command: Option<String>,
+    #[serde(rename = "ESSystemsPath", default = "default_es_systems_path")]
+    pub es_systems_path: PathBuf,
+    /// Resolved while loading, to RetroBat's es_settings.cfg when not set.
+    #[serde(rename = "ESSettingsPath", default)]
+    pub es_settings_path: Option<PathBuf>,
+    #[serde(rename = "ESEventFilePath", default = "default_es_event_file_path")]
+    pub es_event_file_path: PathBuf,
+    #[serde(rename = "GeneratorGradient", default)]
+    pub generator_gradient: Option<String>,
+    #[serde(rename = "GeneratorFanartOffset", default)]
+    pub generator_fanart_offset: Option<f32>,
+    #[serde(rename = "GeneratorLogoAnchor", default)]
+    pub generator_logo_anchor: Option<String>,
+    #[serde(rename = "GeneratorTemplatesPath", default = "default_generator_templates_path")]
+    pub generator_templates_path: PathBuf,
+    #[serde(rename = "GeneratorEncoderCommand", default = "default_generator_encoder_command")]
+    pub generator_encoder_command: String,
+    #[serde(rename = "GeneratorLayoutsPath", default = "default_generator_layouts_path")]
+    pub generator_layouts_path: PathBuf,
+    #[serde(rename = "OverlayEnabled", default, deserialize_with = "deserialize_bool_from_string")]
+    pub overlay_enabled: bool,
+    #[serde(rename = "OverlayTemplate", default = "default_overlay_template")]
+    pub overlay_template: String,
+    #[serde(rename = "OverlaySeparator", default = "default_overlay_separator")]
+    pub overlay_separator: String,
+    #[serde(rename = "OverlayPosition", default = "default_overlay_position")]
+    pub overlay_position: String,
+    #[serde(rename = "OverlayFontSize", default = "default_overlay_font_size")]
+    pub overlay_font_size: i32,
+    #[serde(rename = "OverlayDuration", default = "default_overlay_duration")]
+    pub overlay_duration: i32,
+    #[serde(rename = "DisplayBackend", default)]
+    pub display_backend: Option<String>,
+    #[serde(rename = "HeadlessOutputPath", default)]
+    pub headless_output_path: Option<PathBuf>,
+    #[serde(default = "default_host")]
     pub host: String,
+    #[serde(default = "default_port")]
     pub port: i32,
-    #[serde(rename = "logFile", deserialize_with = "deserialize_bool_from_string")]
+    #[serde(rename = "logFile", default = "default_true", deserialize_with = "deserialize_bool_from_string")]
     pub log_file: bool,
+    /// The folder holding config.ini, which relative paths are resolved against.
+    #[serde(skip)]
+    pub plugin_path: PathBuf,
 }
 
+// The [Commands] used when config.ini has none: every event drives mpv through its IPC pipe
+const DEFAULT_COMMANDS: &[(&str, &str)] = &[
+    ("game-start", "echo loadfile \"{marquee_file}\" > {IPCChannel}"),
+    ("game-end", "echo loadfile \"{DefaultImagePath}\" > {IPCChannel}"),
+    ("screensaver-start", "echo loadfile \"{marquee_file}\" > {IPCChannel}"),
+    ("screensaver-stop", "echo loadfile \"{DefaultImagePath}\" > {IPCChannel}"),
+    ("screensaver-game-select", "echo loadfile \"{marquee_file}\" > {IPCChannel}"),
+    ("system-select", "echo loadfile \"{marquee_file}\" > {IPCChannel}"),
+    ("system-selected", "echo loadfile \"{marquee_file}\" > {IPCChannel}"),
+    ("game-select", "echo loadfile \"{marquee_file}\" > {IPCChannel}"),
+    ("game-selected", "echo loadfile \"{marquee_file}\" > {IPCChannel}"),
+    ("mpv-show-text", "echo show-text \"{message}\" {duration} > {IPCChannel}"),
+];
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Commands {
     #[serde(flatten)]
     pub commands: HashMap<String, String>,
 }
 
+impl Default for Commands {
+    fn default() -> Self {
+        Commands {
+            commands: DEFAULT_COMMANDS.iter().map(|(event, command)| (event.to_string(), command.to_string())).collect(),
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     #[serde(rename = "Settings")]
     pub settings: Settings,
-    #[serde(rename = "Commands")]
+    #[serde(rename = "Commands", default)]
     pub commands: Commands,
+    #[serde(skip)]
+    pub templates: Templates,
+    #[serde(skip)]
+    pub outputs: Vec<Output>,
+    #[serde(skip)]
+    pub overlay: Option<Overlay>,
+    #[serde(skip)]
+    pub profiles: Profiles,
+    #[serde(skip)]
+    pub layout: Layout,
+    #[serde(skip)]
+    pub designs: Designs,
+    #[serde(skip)]
+    pub system_configs: HashMap<String, Arc<Config>>,
+    /// `AcceptedFormats`, parsed.
+    #[serde(skip)]
+    pub formats: AcceptedFormats,
+    /// The user-facing messages in the configured `Language`.
+    #[serde(skip)]
+    pub messages: Catalog,
+}
+
+impl Settings {
+    /// Expands `{RetroBatPath}` and `{PluginPath}` in every path setting, then makes relative
+    /// paths relative to the plugin folder, the folder holding config.ini.
+    fn resolve_paths(&mut self, plugin_path: &Path) -> Result<(), String> {
+        self.plugin_path = plugin_path.to_path_buf();
+        if let Some(retrobat_path) = &self.retrobat_path {
+            self.retrobat_path = Some(plugin_path.join(retrobat_path));
+        }
+        let retrobat_path = self.retrobat_path.clone();
+        let resolve_in = |key: &str, path: &mut PathBuf, theme: &str| -> Result<(), String> {
+            let themed = match path.to_str() {
+                Some(text) => PathBuf::from(text.replace("{Theme}", theme)),
+                None => path.clone(),
+            };
+            *path = resolve_path(&themed, retrobat_path.as_deref(), plugin_path)
+                .map_err(|placeholder| format!("{} uses {{{}}} but RetroBatPath is not set", key, placeholder))?;
+            Ok(())
+        };
+
+        // The frontend settings fill in the language and the {Theme} of the other paths
+        let es_settings_path =
+            es_settings::resolve_path(self.es_settings_path.as_deref(), retrobat_path.as_deref(), plugin_path)
+                .map_err(|placeholder| format!("ESSettingsPath uses {{{}}} but RetroBatPath is not set", placeholder))?;
+        let frontend = EsSettings::load_or_default(&es_settings_path);
+        if self.language.is_none() {
+            self.language = frontend.language().map(str::to_string);
+        }
+        let themes = es_settings_path.parent().unwrap_or(plugin_path).join("themes");
+        self.es_settings_path = Some(es_settings_path);
+        let theme = frontend.theme().filter(|theme| themes.join(theme).is_dir()).unwrap_or(DEFAULT_THEME);
+        let resolve = |key: &str, path: &mut PathBuf| resolve_in(key, path, theme);
+        resolve("MarqueeConvertCachePath", &mut self.marquee_convert_cache_path)?;
+        resolve("MarqueeTextFallbackCachePath", &mut self.marquee_text_fallback_cache_path)?;
+        resolve("GeneratedCachePath", &mut self.generated_cache_path)?;
+        resolve("SvgCachePath", &mut self.svg_cache_path)?;
+        resolve("RomsPath", &mut self.roms_path)?;
+        resolve("DefaultImagePath", &mut self.default_image_path)?;
+        resolve("MarqueeImagePath", &mut self.marquee_image_path)?;
+        resolve("MarqueeImagePathDefault", &mut self.marquee_image_path_default)?;
+        resolve("SystemMarqueePath", &mut self.system_marquee_path)?;
+        resolve("CollectionMarqueePath", &mut self.collection_marquee_path)?;
+        resolve("MPVPath", &mut self.mpv_path)?;
+        resolve("GeneratorTemplatesPath", &mut self.generator_templates_path)?;
+        resolve("GeneratorLayoutsPath", &mut self.generator_layouts_path)?;
+        resolve("ESSystemsPath", &mut self.es_systems_path)?;
+        resolve("ESEventFilePath", &mut self.es_event_file_path)?;
+        if let Some(headless_output_path) = &mut self.headless_output_path {
+            resolve("HeadlessOutputPath", headless_output_path)?;
+        }
+
+        // Eviction and `cache purge` delete files from the cache folders, keep them off the artwork
+        let art_folders = [
+            ("MarqueeImagePath", self.marquee_image_path.as_path()),
+            ("SystemMarqueePath", self.system_marquee_path.as_path()),
+            ("DefaultImagePath", self.default_image_path.parent().unwrap_or(&self.default_image_path)),
+        ];
+        let cache_folders = [
+            ("MarqueeConvertCachePath", &self.marquee_convert_cache_path),
+            ("MarqueeTextFallbackCachePath", &self.marquee_text_fallback_cache_path),
+            ("GeneratedCachePath", &self.generated_cache_path),
+            ("SvgCachePath", &self.svg_cache_path),
+        ];
+        for (cache_key, cache_folder) in cache_folders {
+            if let Some((art_key, _)) = art_folders.iter().find(|(_, art_folder)| cache_folder.starts_with(art_folder)) {
+                return Err(format!("{} must not be inside the {} folder, its files would be deleted", cache_key, art_key));
+            }
+        }
+        Ok(())
+    }
+}
+
+const SYSTEM_SECTION_PREFIX: &str = "System:";
+// The theme RetroBat installs, used for {Theme} when the frontend's theme cannot be found
+const DEFAULT_THEME: &str = "es-theme-carbon";
+
+// Lets [Settings] be deserialized on its own, with a system's overrides merged in
+#[derive(Deserialize)]
+struct SettingsSection {
+    #[serde(rename = "Settings")]
+    settings: Settings,
 }
 
 impl Config {
     pub fn load_config(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path)?;
-        let config = serde_ini::from_str(&content)?;
+        Self::parse_config(path, &fs::read_to_string(path)?)
+    }
+
+    /// Loads `content` as the configuration file at `path`, which relative paths start from.
+    pub fn parse_config(path: &Path, content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config: Config = serde_ini::from_str(content)?;
+
+        config.settings.retrobat_path = find_retrobat_path(config.settings.retrobat_path.take());
+        let plugin_path = plugin_path(path)?;
+        config.apply_settings(&plugin_path)?;
+
+        // Sections beyond [Settings] and [Commands] are read from a generic view of the file
+        let sections: HashMap<String, HashMap<String, String>> = serde_ini::from_str(content)?;
+        config.outputs = outputs::parse_outputs(&sections, &config.settings, &config.templates.mpv_launch)?;
+        config.profiles = Profiles::parse(&sections)?;
+        config.system_configs = config.parse_system_configs(&sections, &plugin_path)?;
         Ok(config)
     }
+
+    /// The configuration to use for `system_name`: its `[System:<name>]` overrides applied
+    /// over `[Settings]`, or the configuration itself when it has none.
+    pub fn for_system(&self, system_name: &str) -> &Config {
+        self.system_configs
+            .get(&system_name.to_lowercase())
+            .map(|config| config.as_ref())
+            .unwrap_or(self)
+    }
+
+    // Derives everything that depends on the settings, validating them on the way
+    fn apply_settings(&mut self, plugin_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.settings.resolve_paths(plugin_path)?;
+        self.templates = Templates::parse(&self.settings, &self.commands.commands)?;
+        self.overlay = Overlay::from_settings(&self.settings)?;
+        self.layout = Layout::from_settings(&self.settings)?;
+        self.designs = Designs::load(&self.settings.generator_templates_path)?;
+        self.formats = AcceptedFormats::parse(&self.settings.accepted_formats);
+        self.messages = messages::catalog(self.settings.language.as_deref().unwrap_or_default());
+        if parse_colour(&self.settings.svg_background).is_none() {
+            return Err(format!(
+                "Invalid SvgBackground '{}', expected #rrggbb, #rrggbbaa or transparent",
+                self.settings.svg_background
+            )
+            .into());
+        }
+        if Fill::parse(&self.settings.marquee_auto_convert_fill).is_none() {
+            return Err(format!(
+                "Invalid MarqueeAutoConvertFill '{}', expected blur, dominant, accent or black",
+                self.settings.marquee_auto_convert_fill
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    // Builds one configuration per [System:<name>] section, keyed by lowercase system name
+    fn parse_system_configs(
+        &self,
+        sections: &HashMap<String, HashMap<String, String>>,
+        plugin_path: &Path,
+    ) -> Result<HashMap<String, Arc<Config>>, Box<dyn std::error::Error>> {
+        let base = sections.get("Settings").cloned().unwrap_or_default();
+        let mut system_configs = HashMap::new();
+        for (section_name, overrides) in sections {
+            let Some(system_name) = section_name.strip_prefix(SYSTEM_SECTION_PREFIX) else {
+                continue;
+            };
+            let mut merged = base.clone();
+            merged.extend(overrides.iter().map(|(key, value)| (key.clone(), value.clone())));
+            let ini: String = std::iter::once("[Settings]".to_string())
+                .chain(merged.iter().map(|(key, value)| format!("{} = {}", key, value)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let SettingsSection { mut settings } = serde_ini::from_str(&ini)
+                .map_err(|e| format!("Invalid [{}] section: {}", section_name, e))?;
+            if settings.retrobat_path.is_none() {
+                settings.retrobat_path = self.settings.retrobat_path.clone();
+            }
+
+            let mut system_config = Config {
+                settings,
+                system_configs: HashMap::new(),
+                ..self.clone()
+            };
+            system_config
+                .apply_settings(plugin_path)
+                .map_err(|e| format!("Invalid [{}] section: {}", section_name, e))?;
+            info!("Loaded settings overrides for system {}", system_name.trim());
+            system_configs.insert(system_name.trim().to_lowercase(), Arc::new(system_config));
+        }
+        Ok(system_configs)
+    }
 }
@@ -0,0 +1,118 @@
+use crate::cache::{self, CacheKey};
+use crate::config::Config;
+use crate::media::MediaKind;
+use crate::palette::Palette;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgba, RgbaImage};
+use log::{error, info};
+use std::path::{Path, PathBuf};
+
+// The blurred background is computed at this fraction of the marquee size
+const BLUR_DOWNSCALE: u32 = 8;
+const BLUR_SIGMA: f32 = 6.0;
+const BLUR_DARKEN: f32 = 0.6;
+
+/// What fills the area around a converted image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fill {
+    /// A blurred, darkened copy of the image stretched over the whole marquee.
+    Blur,
+    /// The most common colour of the image.
+    Dominant,
+    /// The accent colour of the image.
+    Accent,
+    Black,
+}
+
+impl Fill {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "blur" => Some(Fill::Blur),
+            "dominant" => Some(Fill::Dominant),
+            "accent" => Some(Fill::Accent),
+            "black" => Some(Fill::Black),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the file to display for `path`: with `MarqueeAutoConvert` enabled, still images are
+/// converted once to the exact marquee geometry and served from the conversion cache.
+pub fn convert_for_display(path: &Path, config: &Config) -> PathBuf {
+    // Only still images are converted, videos and animations are left to mpv
+    let convertible = MediaKind::of(path).is_some_and(|kind| kind.capabilities().convertible);
+    if !config.settings.marquee_auto_convert || !convertible {
+        return path.to_path_buf();
+    }
+
+    let cache_path = match cached_path(path, config) {
+        Some(cache_path) => cache_path,
+        None => return path.to_path_buf(),
+    };
+    if let Some(cached) = cache::lookup(&cache_path) {
+        return cached;
+    }
+
+    info!("Converting {:?} to {:?}", path, cache_path);
+    let converted = image::open(path).map(|source| convert(&source, config));
+    match converted {
+        Ok(converted) => {
+            match cache::store(&converted, &cache_path, config) {
+                Ok(()) => cache_path,
+                Err(e) => {
+                    error!("Failed to write converted marquee {:?}: {}", cache_path, e);
+                    path.to_path_buf()
+                }
+            }
+        }
+        Err(e) => {
+            error!("Failed to convert {:?}: {}", path, e);
+            path.to_path_buf()
+        }
+    }
+}
+
+// The cache file name changes whenever the source image or the conversion settings change
+fn cached_path(path: &Path, config: &Config) -> Option<PathBuf> {
+    let mut key = CacheKey::new();
+    key.file(path).ok()?;
+    key.geometry(config).text(&config.settings.marquee_auto_convert_fill.to_lowercase());
+    let stem = path.file_stem()?.to_string_lossy();
+    Some(key.path(&config.settings.marquee_convert_cache_path, &stem, "png"))
+}
+
+fn convert(source: &DynamicImage, config: &Config) -> RgbaImage {
+    let width = config.settings.marquee_width.max(1) as u32;
+    let height = config.settings.marquee_height.max(1) as u32;
+    let border = (config.settings.marquee_border.max(0) as u32).min(width.min(height) / 2);
+    let inner_width = (width - 2 * border).max(1);
+    let inner_height = (height - 2 * border).max(1);
+
+    let fill = Fill::parse(&config.settings.marquee_auto_convert_fill).unwrap_or(Fill::Blur);
+    let mut canvas = match fill {
+        Fill::Blur => blurred_background(source, width, height),
+        Fill::Dominant => RgbaImage::from_pixel(width, height, Palette::extract(source).dominant),
+        Fill::Accent => RgbaImage::from_pixel(width, height, Palette::extract(source).accent),
+        Fill::Black => RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255])),
+    };
+
+    let fitted = source.resize(inner_width, inner_height, FilterType::Lanczos3).to_rgba8();
+    let x = border + (inner_width - fitted.width()) / 2;
+    let y = border + (inner_height - fitted.height()) / 2;
+    imageops::overlay(&mut canvas, &fitted, x as i64, y as i64);
+    canvas
+}
+
+fn blurred_background(source: &DynamicImage, width: u32, height: u32) -> RgbaImage {
+    let small = source
+        .resize_to_fill((width / BLUR_DOWNSCALE).max(1), (height / BLUR_DOWNSCALE).max(1), FilterType::Triangle)
+        .blur(BLUR_SIGMA);
+    let mut background = small.resize_exact(width, height, FilterType::Triangle).to_rgba8();
+    for pixel in background.pixels_mut() {
+        for channel in pixel.0.iter_mut().take(3) {
+            *channel = (*channel as f32 * BLUR_DARKEN) as u8;
+        }
+        pixel.0[3] = 255;
+    }
+    background
+}
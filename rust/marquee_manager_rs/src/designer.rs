@@ -0,0 +1,543 @@
+use crate::colour::parse_colour;
+use crate::generator::Gradient;
+use crate::palette::{Palette, PaletteRole};
+use crate::template::{Escape, Template, Value, DESIGN_PLACEHOLDERS, FALLBACK_DESIGN_PLACEHOLDERS};
+use image::Rgba;
+use log::info;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LAYER_SECTION_PREFIX: &str = "Layer:";
+const ANIMATION_SECTION: &str = "Animation";
+// Longest loop in seconds, every frame is rendered and encoded at the full marquee size
+const MAX_ANIMATION_DURATION: f32 = 30.0;
+const DEFAULT_DESIGN_NAME: &str = "default";
+const FALLBACK_DESIGN_NAME: &str = "fallback";
+// The built-in designs, also shipped in templates/ as a starting point
+const BUILTIN_DESIGN: &str = include_str!("../templates/default.ini");
+const BUILTIN_FALLBACK_DESIGN: &str = include_str!("../templates/fallback.ini");
+
+/// Where a layer sits inside the marquee area.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+    /// Follows the logo anchor chosen with F10/F11.
+    Layout,
+}
+
+impl Anchor {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "top-left" => Some(Anchor::TopLeft),
+            "top" => Some(Anchor::Top),
+            "top-right" => Some(Anchor::TopRight),
+            "left" => Some(Anchor::Left),
+            "center" => Some(Anchor::Center),
+            "right" => Some(Anchor::Right),
+            "bottom-left" => Some(Anchor::BottomLeft),
+            "bottom" => Some(Anchor::Bottom),
+            "bottom-right" => Some(Anchor::BottomRight),
+            "layout" => Some(Anchor::Layout),
+            _ => None,
+        }
+    }
+
+    /// Horizontal and vertical alignment, 0.0 being left/top and 1.0 right/bottom.
+    pub fn alignment(self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Center | Anchor::Layout => (0.5, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// How an image is sized to its layer box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fit {
+    /// Fill the box and crop the overflow, following the fanart offset chosen with F8/F9.
+    Cover,
+    /// Fit inside the box, keeping the aspect ratio.
+    Contain,
+    Stretch,
+}
+
+/// A colour, either fixed or taken from the palette of an image layer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    Solid(Rgba<u8>),
+    /// `<layer>.dominant` or `<layer>.accent`, `fallback` when the layer has no image.
+    Palette { layer: String, role: PaletteRole, fallback: Rgba<u8> },
+}
+
+impl Paint {
+    /// The fixed colour, or the colour picked from the palette of the referenced layer.
+    pub fn resolve(&self, palettes: &HashMap<String, Palette>) -> Rgba<u8> {
+        match self {
+            Paint::Solid(colour) => *colour,
+            Paint::Palette { layer, role, fallback } => {
+                palettes.get(layer).map(|palette| palette.get(*role)).unwrap_or(*fallback)
+            }
+        }
+    }
+}
+
+/// How a cover image moves over an animation loop. Motions go back and forth so the loop is
+/// seamless.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Motion {
+    None,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+}
+
+impl Motion {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "none" => Some(Motion::None),
+            "pan-left" => Some(Motion::PanLeft),
+            "pan-right" => Some(Motion::PanRight),
+            "pan-up" => Some(Motion::PanUp),
+            "pan-down" => Some(Motion::PanDown),
+            "zoom-in" => Some(Motion::ZoomIn),
+            "zoom-out" => Some(Motion::ZoomOut),
+            _ => None,
+        }
+    }
+}
+
+/// The container an animated marquee is written to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationFormat {
+    Gif,
+    /// Encoded by `GeneratorEncoderCommand`.
+    Mp4,
+}
+
+impl AnimationFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Mp4 => "mp4",
+        }
+    }
+}
+
+/// The `[Animation]` section: designs that have one produce a looping animation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animation {
+    /// Loop length in seconds.
+    pub duration: f32,
+    pub frame_rate: u32,
+    pub format: AnimationFormat,
+}
+
+impl Animation {
+    fn from_section(design_name: &str, section: &HashMap<String, String>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut animation = Animation { duration: 4.0, frame_rate: 15, format: AnimationFormat::Gif };
+        for (key, value) in section {
+            let value = value.trim();
+            let invalid = |expected: &str| {
+                format!("Invalid {} '{}' in [{}] of design '{}', expected {}", key, value, ANIMATION_SECTION, design_name, expected)
+            };
+            match key.as_str() {
+                "Duration" => {
+                    animation.duration = value
+                        .parse()
+                        .ok()
+                        .filter(|d: &f32| *d > 0.0 && *d <= MAX_ANIMATION_DURATION)
+                        .ok_or_else(|| invalid(&format!("a number of seconds above 0 and up to {}", MAX_ANIMATION_DURATION)))?
+                }
+                "FrameRate" => {
+                    animation.frame_rate = value
+                        .parse()
+                        .ok()
+                        .filter(|r: &u32| (1..=60).contains(r))
+                        .ok_or_else(|| invalid("a number of frames per second between 1 and 60"))?
+                }
+                "Format" => {
+                    animation.format = match value.to_lowercase().as_str() {
+                        "gif" => AnimationFormat::Gif,
+                        "mp4" => AnimationFormat::Mp4,
+                        _ => return Err(invalid("gif or mp4").into()),
+                    }
+                }
+                _ => return Err(format!("Unknown key {} in [{}] of design '{}'", key, ANIMATION_SECTION, design_name).into()),
+            }
+        }
+        Ok(animation)
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        ((self.duration * self.frame_rate as f32).round() as u32).max(1)
+    }
+}
+
+/// A blurred copy of the layer drawn behind it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shadow {
+    pub colour: Paint,
+    /// Distance in pixels, down and to the right.
+    pub offset: i32,
+    pub blur: f32,
+}
+
+#[derive(Debug, Clone)]
+pub enum LayerKind {
+    /// The first existing file among `sources`.
+    Image { sources: Vec<Template>, fit: Fit, required: bool },
+    /// `None` follows the gradient chosen with F6.
+    Gradient { direction: Option<Gradient>, colour: Paint, strength: f32 },
+    Text { text: Template, font: PathBuf, font_size: f32, colour: Paint },
+    /// An image stretched over the whole marquee (bezel), or a solid frame around it.
+    /// Frames are drawn over the border, after every other layer.
+    Frame { source: Option<Template>, colour: Paint, thickness: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    pub order: i32,
+    pub kind: LayerKind,
+    pub anchor: Anchor,
+    /// Offsets and size are fractions of the marquee area, excluding the border.
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub opacity: f32,
+    pub shadow: Option<Shadow>,
+    /// Movement of a cover image in animated designs.
+    pub motion: Motion,
+    /// How far the image moves or zooms, as a fraction of its size.
+    pub motion_amount: f32,
+    /// Seconds the layer takes to fade in at the start of the loop, and out at its end.
+    pub fade: f32,
+}
+
+/// A marquee layout template: layers drawn from the lowest `Order` to the highest.
+#[derive(Debug, Clone)]
+pub struct Design {
+    pub name: String,
+    pub layers: Vec<Layer>,
+    pub animation: Option<Animation>,
+    /// The file content, part of the cache key of the marquees rendered with it.
+    pub definition: String,
+}
+
+impl Design {
+    /// Parses a design whose sources and texts may use the `known` placeholders.
+    pub fn parse(name: &str, content: &str, known: &[&str]) -> Result<Self, Box<dyn std::error::Error>> {
+        let sections: HashMap<String, HashMap<String, String>> = serde_ini::from_str(content)?;
+        let mut layers = Vec::new();
+        let mut animation = None;
+        for (section_name, section) in &sections {
+            if section_name == ANIMATION_SECTION {
+                animation = Some(Animation::from_section(name, section)?);
+                continue;
+            }
+            match section_name.strip_prefix(LAYER_SECTION_PREFIX) {
+                Some(layer_name) => layers.push(Layer::from_section(name, layer_name, section, known)?),
+                None => return Err(format!("Unknown section [{}] in design '{}'", section_name, name).into()),
+            }
+        }
+        // Ties are broken by name so the result does not depend on the file order
+        layers.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.name.cmp(&b.name)));
+        let design = Design { name: name.to_string(), layers, animation, definition: content.to_string() };
+
+        for layer in &design.layers {
+            for referenced in layer.paints().filter_map(|paint| match paint {
+                Paint::Palette { layer, .. } => Some(layer),
+                Paint::Solid(_) => None,
+            }) {
+                let is_image = design.layers.iter().any(|other| {
+                    &other.name == referenced && matches!(other.kind, LayerKind::Image { .. })
+                });
+                if !is_image {
+                    return Err(format!(
+                        "Layer '{}' of design '{}' takes a colour from '{}', which is not an image layer",
+                        layer.name, name, referenced
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(design)
+    }
+
+    /// The image layers whose palette is referenced by a colour.
+    pub fn palette_layers(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().flat_map(|layer| layer.paints()).filter_map(|paint| match paint {
+            Paint::Palette { layer, .. } => Some(layer.as_str()),
+            Paint::Solid(_) => None,
+        })
+    }
+
+    /// The files the marquee is drawn from, or `None` if a required image is missing.
+    pub fn sources(&self, values: &[(&str, Value)]) -> Option<Vec<PathBuf>> {
+        let mut sources = Vec::new();
+        for layer in &self.layers {
+            match &layer.kind {
+                LayerKind::Image { sources: candidates, required, .. } => match first_existing(candidates, values) {
+                    Some(path) => sources.push(path),
+                    None if *required => return None,
+                    None => {}
+                },
+                LayerKind::Frame { source: Some(source), .. } => {
+                    sources.extend(first_existing(std::slice::from_ref(source), values))
+                }
+                _ => {}
+            }
+        }
+        Some(sources)
+    }
+
+    // Relative font paths are relative to the folder of the design file
+    fn resolve_fonts(&mut self, directory: &Path) {
+        for layer in &mut self.layers {
+            if let LayerKind::Text { font, .. } = &mut layer.kind {
+                *font = directory.join(&*font);
+            }
+        }
+    }
+}
+
+/// Renders each candidate path and returns the first one that exists.
+pub fn first_existing(candidates: &[Template], values: &[(&str, Value)]) -> Option<PathBuf> {
+    candidates
+        .iter()
+        .filter_map(|candidate| candidate.render(values).ok())
+        .map(PathBuf::from)
+        .find(|path| path.exists())
+}
+
+impl Layer {
+    fn paints(&self) -> impl Iterator<Item = &Paint> {
+        let kind_paint = match &self.kind {
+            LayerKind::Gradient { colour, .. } | LayerKind::Text { colour, .. } | LayerKind::Frame { colour, .. } => {
+                Some(colour)
+            }
+            LayerKind::Image { .. } => None,
+        };
+        kind_paint.into_iter().chain(self.shadow.as_ref().map(|shadow| &shadow.colour))
+    }
+
+    fn from_section(
+        design_name: &str,
+        layer_name: &str,
+        section: &HashMap<String, String>,
+        known: &[&str],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let context = format!("[{}{}] of design '{}'", LAYER_SECTION_PREFIX, layer_name, design_name);
+        let get = |key: &str| section.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+        let invalid = |key: &str, expected: &str| {
+            format!("Invalid {} '{}' in {}, expected {}", key, get(key).unwrap_or(""), context, expected)
+        };
+        let number = |key: &str, default: f32| -> Result<f32, String> {
+            get(key).map(|v| v.parse().map_err(|_| invalid(key, "a number"))).unwrap_or(Ok(default))
+        };
+        let colour = |key: &str, default: Rgba<u8>| -> Result<Paint, String> {
+            let expected = "#rrggbb, #rrggbbaa, transparent, <layer>.dominant or <layer>.accent";
+            match get(key) {
+                None => Ok(Paint::Solid(default)),
+                Some(value) => match parse_colour(value) {
+                    Some(colour) => Ok(Paint::Solid(colour)),
+                    None => {
+                        // `<layer>.<role>|<colour>` overrides the colour used when the layer has no image
+                        let (reference, fallback) = match value.split_once('|') {
+                            Some((reference, fallback)) => {
+                                (reference, parse_colour(fallback).ok_or_else(|| invalid(key, expected))?)
+                            }
+                            None => (value, default),
+                        };
+                        let (layer, role) = reference.rsplit_once('.').ok_or_else(|| invalid(key, expected))?;
+                        let role = PaletteRole::parse(role).ok_or_else(|| invalid(key, expected))?;
+                        Ok(Paint::Palette { layer: layer.trim().to_string(), role, fallback })
+                    }
+                },
+            }
+        };
+        let template = |key: &str, source: &str| {
+            Template::parse(&format!("{} {}", context, key), source.trim(), known, Escape::Raw)
+        };
+        let required = |key: &str| get(key).ok_or_else(|| format!("Missing {} in {}", key, context));
+
+        let layer_type = required("Type")?.to_lowercase();
+        let (kind, known_keys): (LayerKind, &[&str]) = match layer_type.as_str() {
+            "image" => {
+                let sources = required("Source")?
+                    .split('|')
+                    .map(|source| template("Source", source))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let fit = match get("Fit").unwrap_or("contain").to_lowercase().as_str() {
+                    "cover" => Fit::Cover,
+                    "contain" => Fit::Contain,
+                    "stretch" => Fit::Stretch,
+                    _ => return Err(invalid("Fit", "cover, contain or stretch").into()),
+                };
+                let required = match get("Required").map(|v| v.to_lowercase()) {
+                    None => true,
+                    Some(v) if v == "true" => true,
+                    Some(v) if v == "false" => false,
+                    Some(_) => return Err(invalid("Required", "true or false").into()),
+                };
+                (LayerKind::Image { sources, fit, required }, &["Source", "Fit", "Required"])
+            }
+            "gradient" => {
+                let direction = match get("Direction").unwrap_or("layout") {
+                    d if d.eq_ignore_ascii_case("layout") => None,
+                    d => Some(Gradient::parse(d).ok_or_else(|| invalid("Direction", "none, left, right, bottom, full or layout"))?),
+                };
+                let kind = LayerKind::Gradient {
+                    direction,
+                    colour: colour("Colour", Rgba([0, 0, 0, 255]))?,
+                    strength: number("Strength", 0.85)?.clamp(0.0, 1.0),
+                };
+                (kind, &["Direction", "Colour", "Strength"])
+            }
+            "text" => {
+                let kind = LayerKind::Text {
+                    text: template("Text", required("Text")?)?,
+                    font: PathBuf::from(required("Font")?),
+                    font_size: number("FontSize", 0.3)?,
+                    colour: colour("Colour", Rgba([255, 255, 255, 255]))?,
+                };
+                (kind, &["Text", "Font", "FontSize", "Colour"])
+            }
+            "frame" => {
+                let kind = LayerKind::Frame {
+                    source: get("Source").map(|source| template("Source", source)).transpose()?,
+                    colour: colour("Colour", Rgba([0, 0, 0, 255]))?,
+                    thickness: number("Thickness", 0.0)?.max(0.0) as u32,
+                };
+                (kind, &["Source", "Colour", "Thickness"])
+            }
+            _ => return Err(invalid("Type", "image, gradient, text or frame").into()),
+        };
+
+        const COMMON_KEYS: &[&str] = &[
+            "Type", "Order", "Anchor", "OffsetX", "OffsetY", "Width", "Height", "Opacity",
+            "ShadowColour", "ShadowOffset", "ShadowBlur", "Motion", "MotionAmount", "Fade",
+        ];
+        if let Some(key) = section.keys().find(|key| !COMMON_KEYS.contains(&key.as_str()) && !known_keys.contains(&key.as_str())) {
+            return Err(format!("Unknown key {} in {}", key, context).into());
+        }
+
+        let motion = match get("Motion") {
+            None => Motion::None,
+            Some(value) => {
+                let motion = Motion::parse(value)
+                    .ok_or_else(|| invalid("Motion", "none, pan-left, pan-right, pan-up, pan-down, zoom-in or zoom-out"))?;
+                if motion != Motion::None && !matches!(kind, LayerKind::Image { fit: Fit::Cover, .. }) {
+                    return Err(format!("Motion in {} needs an image layer with Fit = cover", context).into());
+                }
+                motion
+            }
+        };
+
+        let shadow = match get("ShadowColour") {
+            Some(_) => Some(Shadow {
+                colour: colour("ShadowColour", Rgba([0, 0, 0, 255]))?,
+                offset: number("ShadowOffset", 4.0)? as i32,
+                blur: number("ShadowBlur", 4.0)?.max(0.0),
+            }),
+            None => None,
+        };
+
+        Ok(Layer {
+            name: layer_name.to_string(),
+            order: get("Order").map(|v| v.parse().map_err(|_| invalid("Order", "an integer"))).unwrap_or(Ok(0))?,
+            kind,
+            anchor: get("Anchor")
+                .map(|v| Anchor::parse(v).ok_or_else(|| invalid("Anchor", "top-left, top, top-right, left, center, right, bottom-left, bottom, bottom-right or layout")))
+                .unwrap_or(Ok(Anchor::Center))?,
+            offset_x: number("OffsetX", 0.0)?,
+            offset_y: number("OffsetY", 0.0)?,
+            width: number("Width", 1.0)?.clamp(0.0, 1.0),
+            height: number("Height", 1.0)?.clamp(0.0, 1.0),
+            opacity: number("Opacity", 1.0)?.clamp(0.0, 1.0),
+            shadow,
+            motion,
+            motion_amount: number("MotionAmount", 0.15)?.clamp(0.0, 1.0),
+            fade: number("Fade", 0.0)?.max(0.0),
+        })
+    }
+}
+
+/// The designs found in `GeneratorTemplatesPath`: `<system>.ini` applies to one system and
+/// `default.ini` to every other one, while `fallback.ini` renders the text fallback marquee.
+/// Missing `default.ini` and `fallback.ini` files are replaced by the built-in designs.
+#[derive(Debug, Clone)]
+pub struct Designs {
+    by_system: HashMap<String, Design>,
+    default: Design,
+    pub fallback: Design,
+}
+
+impl Designs {
+    pub fn load(directory: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut by_system = HashMap::new();
+        if let Ok(entries) = fs::read_dir(directory) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if !path.extension().map(|ext| ext.eq_ignore_ascii_case("ini")).unwrap_or(false) {
+                    continue;
+                }
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                    let content = fs::read_to_string(&path)?;
+                    let known = if name.eq_ignore_ascii_case(FALLBACK_DESIGN_NAME) {
+                        FALLBACK_DESIGN_PLACEHOLDERS
+                    } else {
+                        DESIGN_PLACEHOLDERS
+                    };
+                    let mut design = Design::parse(name, &content, known)?;
+                    design.resolve_fonts(directory);
+                    by_system.insert(name.to_lowercase(), design);
+                }
+            }
+        }
+        let default = match by_system.remove(DEFAULT_DESIGN_NAME) {
+            Some(design) => design,
+            None => Design::parse(DEFAULT_DESIGN_NAME, BUILTIN_DESIGN, DESIGN_PLACEHOLDERS)?,
+        };
+        let fallback = match by_system.remove(FALLBACK_DESIGN_NAME) {
+            Some(design) => design,
+            None => Design::parse(FALLBACK_DESIGN_NAME, BUILTIN_FALLBACK_DESIGN, FALLBACK_DESIGN_PLACEHOLDERS)?,
+        };
+        info!("Loaded {} system marquee designs from {:?}", by_system.len(), directory);
+        Ok(Designs { by_system, default, fallback })
+    }
+
+    pub fn for_system(&self, system_name: &str) -> &Design {
+        self.by_system.get(&system_name.to_lowercase()).unwrap_or(&self.default)
+    }
+}
+
+impl Default for Designs {
+    fn default() -> Self {
+        Designs {
+            by_system: HashMap::new(),
+            default: Design { name: DEFAULT_DESIGN_NAME.to_string(), layers: Vec::new(), animation: None, definition: String::new() },
+            fallback: Design { name: FALLBACK_DESIGN_NAME.to_string(), layers: Vec::new(), animation: None, definition: String::new() },
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use crate::config::Config;
+use crate::process;
+use crate::profiles::{PlaybackProfile, Scale};
+use crate::template::Value;
+use marquee_core::ipc;
+use serde_json::json;
+use std::path::Path;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const FADE_STEPS: u32 = 10;
+
+/// How the display switches from the current content to the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transition {
+    Cut,
+    Fade(Duration),
+}
+
+impl Transition {
+    /// Parses `cut` or `fade[:milliseconds]`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        match value.split_once(':') {
+            None if value == "cut" => Some(Transition::Cut),
+            None if value == "fade" => Some(Transition::Fade(Duration::from_millis(300))),
+            Some(("fade", ms)) => ms.trim().parse().ok().map(|ms| Transition::Fade(Duration::from_millis(ms))),
+            _ => None,
+        }
+    }
+}
+
+/// Where and how long a text message is shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextStyle {
+    pub duration: Duration,
+    pub font_size: i32,
+    /// `left`, `center` or `right`
+    pub align_x: String,
+    /// `top`, `center` or `bottom`
+    pub align_y: String,
+}
+
+impl TextStyle {
+    /// mpv's default OSD placement, used for status messages.
+    pub fn message(duration: Duration) -> Self {
+        TextStyle {
+            duration,
+            font_size: 55,
+            align_x: "left".to_string(),
+            align_y: "top".to_string(),
+        }
+    }
+
+    // ASS override tags giving the message its own size and placement, so mpv's OSD options
+    // stay as they are for other messages. The font size is in OSD units, like osd-font-size
+    fn ass_override(&self) -> String {
+        let row = match self.align_y.as_str() {
+            "bottom" => 1,
+            "center" => 4,
+            _ => 7,
+        };
+        let column = match self.align_x.as_str() {
+            "left" => 0,
+            "center" => 1,
+            _ => 2,
+        };
+        format!("{{\\an{}\\fs{}}}", row + column, self.font_size)
+    }
+
+    /// Parses a position such as `bottom-center`, `top-left` or `center`.
+    pub fn parse_position(position: &str) -> Option<(String, String)> {
+        let position = position.trim().to_lowercase();
+        let (vertical, horizontal) = position.split_once('-').unwrap_or((&position, "center"));
+        let align_y = match vertical {
+            "top" | "center" | "bottom" => vertical,
+            _ => return None,
+        };
+        let align_x = match horizontal {
+            "left" | "center" | "right" => horizontal,
+            _ => return None,
+        };
+        Some((align_x.to_string(), align_y.to_string()))
+    }
+}
+
+/// A surface the manager can put marquees on.
+pub trait Display: Send + Sync {
+    /// Shows an image or video file.
+    fn show_media(&self, path: &Path, config: &Config);
+    /// Shows a text message on top of the current content.
+    fn show_text(&self, text: &str, style: &TextStyle, config: &Config);
+    /// Removes the current content.
+    fn clear(&self, config: &Config);
+    /// Moves the display to another screen, for systems with their own `ScreenNumber`.
+    fn move_to_screen(&self, screen: i32) {
+        // Only windowed backends have a screen
+        let _ = screen;
+    }
+    /// Shows a file using the given transition.
+    fn transition(&self, path: &Path, transition: Transition, config: &Config) {
+        // Backends without transition support simply cut
+        let _ = transition;
+        self.show_media(path, config);
+    }
+}
+
+/// A display backed by an mpv instance listening on an IPC pipe.
+pub struct MpvDisplay {
+    ipc_channel: String,
+    // The screen mpv is on, so it is only moved when a system asks for another one
+    screen: AtomicI32,
+}
+
+impl MpvDisplay {
+    pub fn new(ipc_channel: &str, screen: i32) -> Self {
+        MpvDisplay {
+            ipc_channel: ipc_channel.to_string(),
+            screen: AtomicI32::new(screen),
+        }
+    }
+
+    fn set_property(&self, name: &str, value: serde_json::Value) {
+        let command = json!({ "command": ["set_property", name, value] });
+        ipc::send_command(&self.ipc_channel, &command.to_string());
+    }
+
+    // These are options for the next file, so they must be set before it is loaded
+    fn apply_profile(&self, profile: &PlaybackProfile) {
+        self.set_property("loop-file", json!(if profile.loop_file { "inf" } else { "no" }));
+        self.set_property("keep-open", json!(if profile.loop_file { "no" } else { "yes" }));
+        self.set_property("mute", json!(if profile.mute { "yes" } else { "no" }));
+        self.set_property("start", json!(profile.start.to_string()));
+        let image_duration = profile.image_duration.map(|d| d.to_string()).unwrap_or_else(|| "inf".to_string());
+        self.set_property("image-display-duration", json!(image_duration));
+        let (keepaspect, panscan) = match profile.scale {
+            Scale::Fit => ("yes", 0.0),
+            Scale::Fill => ("yes", 1.0),
+            Scale::Stretch => ("no", 0.0),
+        };
+        self.set_property("keepaspect", json!(keepaspect));
+        self.set_property("panscan", json!(panscan));
+    }
+
+    fn set_brightness(&self, brightness: i64) {
+        self.set_property("brightness", json!(brightness));
+    }
+}
+
+impl Display for MpvDisplay {
+    fn show_media(&self, path: &Path, config: &Config) {
+        self.apply_profile(&config.profiles.for_file(path));
+        process::update_marquee(path, &self.ipc_channel, config);
+    }
+
+    fn show_text(&self, text: &str, style: &TextStyle, config: &Config) {
+        // show-text expands ${property} references, `$$` is a literal dollar sign. osd-ass-cc
+        // turns ASS tags on for the style only, the message itself is shown as written
+        let text = format!("${{osd-ass-cc/0}}{}${{osd-ass-cc/1}}{}", style.ass_override(), text.replace('$', "$$"));
+        let duration_ms = style.duration.as_millis().to_string();
+        if config.templates.commands.contains_key("mpv-show-text") {
+            process::run_event_command(
+                "mpv-show-text",
+                &[
+                    ("message", Value::from(text.as_str())),
+                    ("duration", Value::from(duration_ms.as_str())),
+                    ("IPCChannel", Value::from(&self.ipc_channel)),
+                    ("DefaultImagePath", Value::from(&config.settings.default_image_path)),
+                ],
+                &self.ipc_channel,
+                config,
+            );
+        } else {
+            let command = json!({ "command": ["show-text", text, duration_ms] });
+            ipc::send_command(&self.ipc_channel, &command.to_string());
+        }
+    }
+
+    fn move_to_screen(&self, screen: i32) {
+        if self.screen.swap(screen, Ordering::SeqCst) != screen {
+            self.set_property("screen", json!(screen));
+            self.set_property("fs-screen", json!(screen));
+        }
+    }
+
+    fn clear(&self, _config: &Config) {
+        ipc::send_command(&self.ipc_channel, &json!({ "command": ["stop"] }).to_string());
+    }
+
+    fn transition(&self, path: &Path, transition: Transition, config: &Config) {
+        match transition {
+            Transition::Cut => self.show_media(path, config),
+            Transition::Fade(duration) => {
+                // mpv has no cross-fade between files, so fade through black using brightness
+                let step = duration / (FADE_STEPS * 2);
+                for i in 1..=FADE_STEPS {
+                    self.set_brightness(-100 * i as i64 / FADE_STEPS as i64);
+                    thread::sleep(step);
+                }
+                self.show_media(path, config);
+                for i in (0..FADE_STEPS).rev() {
+                    self.set_brightness(-100 * i as i64 / FADE_STEPS as i64);
+                    thread::sleep(step);
+                }
+            }
+        }
+    }
+}
@@ -1,47 +1,73 @@
 use crate::config::Config;
+use crate::gamelist::GamelistCache;
+use crate::layouts::LayoutStore;
 use crate::marquee::{self, MarqueeType};
-use crate::process;
+use crate::outputs::OutputManager;
+use crate::reload;
 use crate::state::AppState;
 use log::{error, info};
+use marquee_core::systems;
 
 use notify::{Config as NotifyConfig, RecommendedWatcher, RecursiveMode, Watcher};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use std::sync::{mpsc::channel, Arc, Mutex};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use url::form_urlencoded;
 
+// How often the watcher checks whether a reload changed ESEventFilePath
+const EVENT_FILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Handles every event EmulationStation writes to `ESEventFilePath`, moving the watch when a
+/// reload points the setting at another file.
 pub fn start_watching(
-    config: &Config,
-    systems: &HashMap<String, String>,
+    config: &RwLock<Arc<Config>>,
+    systems: &RwLock<Arc<HashMap<String, String>>>,
     app_state: Arc<Mutex<AppState>>,
+    outputs: Arc<OutputManager>,
+    layouts: Arc<Mutex<LayoutStore>>,
 ) -> notify::Result<()> {
     let (tx, rx) = channel();
     let mut watcher: RecommendedWatcher = Watcher::new(tx, NotifyConfig::default())?;
-    let event_file_path = Path::new("ESEvent.arg");
+    let mut watched: Option<PathBuf> = None;
+    let mut gamelists = GamelistCache::default();
 
-    // Ensure the file exists before watching
-    if !event_file_path.exists() {
-        if let Err(e) = fs::File::create(&event_file_path) {
-            error!("Failed to create event file: {}", e);
+    loop {
+        let event_file_path = reload::current(config).settings.es_event_file_path.clone();
+        if watched.as_ref() != Some(&event_file_path) {
+            if let Some(previous) = watched.replace(event_file_path.clone()) {
+                let _ = watcher.unwatch(&previous);
+            }
+            // Ensure the file exists before watching
+            if !event_file_path.exists() {
+                if let Err(e) = fs::File::create(&event_file_path) {
+                    error!("Failed to create event file: {}", e);
+                }
+            }
+            match watcher.watch(&event_file_path, RecursiveMode::NonRecursive) {
+                Ok(()) => info!("Watching for events in {:?}...", event_file_path),
+                Err(e) => error!("Failed to watch event file {:?}: {}", event_file_path, e),
+            }
         }
-    }
-
-    watcher.watch(&event_file_path, RecursiveMode::NonRecursive)?;
-
-    info!("Watching for events in ESEvent.arg...");
 
-    loop {
-        match rx.recv() {
+        match rx.recv_timeout(EVENT_FILE_CHECK_INTERVAL) {
             Ok(Ok(event)) => {
                 if let notify::EventKind::Modify(_) = event.kind {
                     if let Ok(content) = fs::read_to_string(&event_file_path) {
-                        handle_event(&content, config, systems, &app_state);
+                        let config = reload::current(config);
+                        let systems = reload::current(systems);
+                        handle_event(&content, &config, &systems, &app_state, &outputs, &layouts, &mut gamelists);
                     }
                 }
             }
             Ok(Err(e)) => error!("Watch error: {:?}", e),
-            Err(e) => error!("Channel receive error: {:?}", e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                error!("Event watch channel closed");
+                return Ok(());
+            }
         }
     }
 }
@@ -51,6 +77,9 @@ fn handle_event(
     config: &Config,
     systems: &HashMap<String, String>,
     app_state: &Arc<Mutex<AppState>>,
+    outputs: &OutputManager,
+    layouts: &Mutex<LayoutStore>,
+    gamelists: &mut GamelistCache,
 ) {
     let params: HashMap<String, String> = form_urlencoded::parse(content.as_bytes())
         .into_owned()
@@ -62,24 +91,47 @@ fn handle_event(
 
     info!("Event received: {}, param1: {}, param2: {}", event, param1, param2);
 
-    let marquee_type = match event.as_str() {
-        "system-selected" => Some(MarqueeType::System {
-            system_name: &param1,
-        }),
+    let (system_name, game_name) = match event.as_str() {
+        "system-selected" => (param1, String::new()),
         "game-selected" => {
             let mut state = app_state.lock().unwrap();
             state.current_game = Some((param1.clone(), param2.clone()));
-            Some(MarqueeType::Game {
-                system_name: &param1,
-                game_name: &param2,
-            })
+            (param1, param2)
         }
-        _ => None,
+        // Other events apply to the game that was last selected
+        _ => app_state.lock().unwrap().current_game.clone().unwrap_or_default(),
+    };
+
+    // Everything below reads the settings of the system, with its [System:<name>] overrides
+    let config = config.for_system(&system_name);
+
+    let metadata = if game_name.is_empty() {
+        None
+    } else {
+        let system_folder = systems::folder(systems, &system_name);
+        gamelists.find(&config.settings.roms_path, system_folder, &game_name)
     };
 
-    if let Some(mt) = marquee_type {
-        let marquee_file = marquee::find_marquee_file(mt, config, systems);
-        info!("Updating marquee to: {:?}", marquee_file);
-        process::update_marquee(&marquee_file, config);
+    let resolve_marquee = || {
+        let marquee_type = if game_name.is_empty() {
+            MarqueeType::System { system_name: &system_name }
+        } else {
+            let display_name = metadata.as_ref().and_then(|m| m.name.as_deref());
+            let layout = layouts.lock().unwrap().get(&system_name, &game_name, &config.layout);
+            MarqueeType::Game { system_name: &system_name, game_name: &game_name, display_name, layout }
+        };
+        marquee::find_marquee_file(marquee_type, config, systems)
+    };
+
+    outputs.handle_event(&event, &system_name, &game_name, resolve_marquee, config);
+
+    if event == "game-selected" {
+        if let Some(overlay) = &config.overlay {
+            let metadata = metadata.unwrap_or_default();
+            if let Some(text) = overlay.render(&metadata, &system_name, &game_name) {
+                info!("Showing overlay: {}", text);
+                outputs.show_text(&text, &overlay.style, config);
+            }
+        }
     }
 }
@@ -0,0 +1,97 @@
+use crate::cache::{self, CacheKey};
+use crate::config::Config;
+use crate::generator;
+use crate::media::MediaKind;
+use crate::template::Value;
+use log::{error, info};
+use std::path::{Path, PathBuf};
+
+/// Renders the `fallback` design for a game without any marquee: its name, from gamelist.xml or
+/// the cleaned ROM name, next to the system logo. Returns `None` when `MarqueeTextFallback` is off.
+pub fn text_fallback_marquee(
+    system_name: &str,
+    system_folder: &str,
+    game_name: &str,
+    display_name: Option<&str>,
+    system_logo: Option<&Path>,
+    config: &Config,
+) -> Option<PathBuf> {
+    if !config.settings.marquee_text_fallback {
+        return None;
+    }
+    let name = match display_name.map(str::trim).filter(|name| !name.is_empty()) {
+        Some(name) => name.to_string(),
+        None => clean_rom_name(game_name),
+    };
+    // Videos and animations cannot be composed, the design simply goes without a logo
+    let system_logo = system_logo.filter(|logo| MediaKind::of(logo).is_some_and(|kind| !kind.capabilities().timed));
+
+    let cache_path = cached_path(system_name, game_name, &name, system_logo, config)?;
+    if let Some(cached) = cache::lookup(&cache_path) {
+        return Some(cached);
+    }
+
+    info!("Rendering text fallback marquee {:?} for '{}'", cache_path, name);
+    let images_path = generator::images_path(system_folder, config);
+    let mut values = generator::design_values(system_name, game_name, &images_path, config);
+    values.push(("name", Value::from(name.as_str())));
+    values.push(("system_logo", system_logo.map(Value::from).unwrap_or_else(|| Value::from(""))));
+
+    let rendered = generator::compose_marquee(&config.designs.fallback, &values, &config.layout, config);
+    match rendered {
+        Ok(marquee) => {
+            match cache::store(&marquee, &cache_path, config) {
+                Ok(()) => Some(cache_path),
+                Err(e) => {
+                    error!("Failed to write text fallback marquee {:?}: {}", cache_path, e);
+                    None
+                }
+            }
+        }
+        Err(e) => {
+            error!("Failed to render text fallback marquee for '{}': {}", name, e);
+            None
+        }
+    }
+}
+
+/// Turns a ROM name such as `Super_Mario_World_(USA)_[!]` into `Super Mario World`.
+pub fn clean_rom_name(game_name: &str) -> String {
+    let mut cleaned = String::new();
+    let mut depth = 0;
+    for c in game_name.chars() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = (depth - 1).max(0),
+            '_' if depth == 0 => cleaned.push(' '),
+            _ if depth == 0 => cleaned.push(c),
+            _ => {}
+        }
+    }
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    if cleaned.is_empty() {
+        game_name.to_string()
+    } else {
+        cleaned
+    }
+}
+
+// The cache file name changes with the name, the logo, the design and the marquee geometry
+fn cached_path(
+    system_name: &str,
+    game_name: &str,
+    name: &str,
+    system_logo: Option<&Path>,
+    config: &Config,
+) -> Option<PathBuf> {
+    let mut key = CacheKey::new();
+    key.text(&config.designs.fallback.definition).text(name).geometry(config);
+    if let Some(logo) = system_logo {
+        key.file(logo).ok()?;
+    }
+    Some(key.path(
+        &config.settings.marquee_text_fallback_cache_path,
+        &format!("{}-{}", system_name, game_name),
+        "png",
+    ))
+}
@@ -0,0 +1,92 @@
+use quick_xml::de::from_str;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// The metadata EmulationStation scraped for a game in gamelist.xml.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct GameMetadata {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub releasedate: Option<String>,
+    #[serde(default)]
+    pub developer: Option<String>,
+    #[serde(default)]
+    pub publisher: Option<String>,
+    #[serde(default)]
+    pub genre: Option<String>,
+    #[serde(default)]
+    pub players: Option<String>,
+    #[serde(default)]
+    pub rating: Option<String>,
+}
+
+impl GameMetadata {
+    /// The year part of `releasedate`, which ES stores as `YYYYMMDDTHHMMSS`.
+    pub fn year(&self) -> Option<&str> {
+        self.releasedate
+            .as_deref()
+            .filter(|date| date.len() >= 4 && date[..4].chars().all(|c| c.is_ascii_digit()))
+            .map(|date| &date[..4])
+    }
+
+    /// The ROM file name without its extension, which is how ES names the game in events.
+    pub fn rom_stem(&self) -> Option<&str> {
+        Path::new(&self.path).file_stem().and_then(|s| s.to_str())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GameList {
+    #[serde(rename = "game", default)]
+    games: Vec<GameMetadata>,
+}
+
+/// gamelist.xml files parsed so far, re-read only when the file changes on disk.
+#[derive(Default)]
+pub struct GamelistCache {
+    entries: HashMap<PathBuf, (SystemTime, Arc<Vec<GameMetadata>>)>,
+}
+
+impl GamelistCache {
+    pub fn find(&mut self, roms_path: &Path, system_folder: &str, game_name: &str) -> Option<GameMetadata> {
+        let gamelist_path = roms_path.join(system_folder).join("gamelist.xml");
+        let games = self.load(&gamelist_path)?;
+        games
+            .iter()
+            .find(|game| game.rom_stem() == Some(game_name))
+            .or_else(|| games.iter().find(|game| game.name.as_deref() == Some(game_name)))
+            .cloned()
+    }
+
+    fn load(&mut self, gamelist_path: &Path) -> Option<Arc<Vec<GameMetadata>>> {
+        let modified = fs::metadata(gamelist_path).and_then(|m| m.modified()).ok()?;
+        if let Some((cached_modified, games)) = self.entries.get(gamelist_path) {
+            if *cached_modified == modified {
+                return Some(games.clone());
+            }
+        }
+
+        let games = match load_gamelist(gamelist_path) {
+            Ok(games) => Arc::new(games),
+            Err(e) => {
+                log::error!("Failed to read {:?}: {}", gamelist_path, e);
+                return None;
+            }
+        };
+        self.entries.insert(gamelist_path.to_path_buf(), (modified, games.clone()));
+        Some(games)
+    }
+}
+
+pub fn load_gamelist(gamelist_path: &Path) -> Result<Vec<GameMetadata>, Box<dyn std::error::Error>> {
+    let xml_content = fs::read_to_string(gamelist_path)?;
+    let game_list: GameList = from_str(&xml_content)?;
+    Ok(game_list.games)
+}
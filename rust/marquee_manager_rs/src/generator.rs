@@ -1,48 +1,643 @@
-use crate::config::Config;
+use crate::cache::{self, CacheKey};
+use crate::config::{Config, Settings};
+use crate::designer::{self, Anchor, Animation, AnimationFormat, Design, Fit, Layer, LayerKind, Motion};
+use crate::palette::Palette;
+use crate::process;
+use crate::svg;
+use crate::template::Value;
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{self, FilterType};
+use image::{Delay, DynamicImage, Frame, GenericImageView, Rgba, RgbaImage};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+
+// Distance the fanart crop moves per F8/F9 press
+pub const FANART_OFFSET_STEP: f32 = 0.1;
+// 1 is the best and slowest GIF quantization, 30 the fastest
+const GIF_ENCODER_SPEED: i32 = 10;
+// Strength of the `full` gradient relative to the edge of the directional ones
+const FULL_GRADIENT_STRENGTH: f32 = 0.6;
+
+/// The darkening gradient drawn between the fanart and the logo.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Gradient {
+    None,
+    /// Dark on the left, fading out to the right.
+    #[default]
+    Left,
+    /// Dark on the right, fading out to the left.
+    Right,
+    /// Dark at the bottom, fading out to the top.
+    Bottom,
+    /// A uniform dark veil.
+    Full,
+}
+
+/// Where the logo is placed.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogoAnchor {
+    #[default]
+    Left,
+    Center,
+}
+
+/// The adjustable parts of a generated marquee.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    pub gradient: Gradient,
+    /// Which part of the fanart is kept when it is cropped: 0.0 is the top, 1.0 the bottom.
+    pub fanart_offset: f32,
+    pub logo_anchor: LogoAnchor,
+}
+
+impl Gradient {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "none" => Some(Gradient::None),
+            "left" => Some(Gradient::Left),
+            "right" => Some(Gradient::Right),
+            "bottom" => Some(Gradient::Bottom),
+            "full" => Some(Gradient::Full),
+            _ => None,
+        }
+    }
+
+    /// The next gradient in the F6 cycle.
+    pub fn next(self) -> Self {
+        match self {
+            Gradient::None => Gradient::Left,
+            Gradient::Left => Gradient::Right,
+            Gradient::Right => Gradient::Bottom,
+            Gradient::Bottom => Gradient::Full,
+            Gradient::Full => Gradient::None,
+        }
+    }
+}
+
+impl LogoAnchor {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "left" => Some(LogoAnchor::Left),
+            "center" => Some(LogoAnchor::Center),
+            _ => None,
+        }
+    }
+}
+
+impl Layout {
+    /// The layout configured by the `Generator*` settings.
+    pub fn from_settings(settings: &Settings) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut layout = Layout::default();
+        if let Some(value) = &settings.generator_gradient {
+            layout.gradient = Gradient::parse(value)
+                .ok_or_else(|| format!("Invalid GeneratorGradient '{}', expected none, left, right, bottom or full", value))?;
+        }
+        if let Some(offset) = settings.generator_fanart_offset {
+            if !(0.0..=1.0).contains(&offset) {
+                return Err(format!("Invalid GeneratorFanartOffset '{}', expected a value between 0 and 1", offset).into());
+            }
+            layout.fanart_offset = offset;
+        }
+        if let Some(value) = &settings.generator_logo_anchor {
+            layout.logo_anchor = LogoAnchor::parse(value)
+                .ok_or_else(|| format!("Invalid GeneratorLogoAnchor '{}', expected left or center", value))?;
+        }
+        Ok(layout)
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            gradient: Gradient::default(),
+            fanart_offset: 0.5,
+            logo_anchor: LogoAnchor::default(),
+        }
+    }
+}
+
+/// Where the generated marquee of a game is cached, named after a hash of its design, source
+/// images and layout. `None` if the design lacks a required image.
+pub fn generated_marquee_path(
+    system_name: &str,
+    system_folder: &str,
+    game_name: &str,
+    layout: &Layout,
+    config: &Config,
+) -> Option<PathBuf> {
+    let design = config.designs.for_system(system_name);
+    let mut key = CacheKey::new();
+    key.text(&design.definition).text(&serde_json::to_string(layout).ok()?).geometry(config);
+    for source in find_sources(system_name, system_folder, game_name, config)? {
+        key.file(&source).ok()?;
+    }
+    let extension = design.animation.map(|animation| animation.format.extension()).unwrap_or("png");
+    Some(key.path(&config.settings.generated_cache_path, &format!("{}-{}", system_name, game_name), extension))
+}
+
+/// The generated marquee of a game, if it is cached for its current sources and layout.
+pub fn cached_marquee(
+    system_name: &str,
+    system_folder: &str,
+    game_name: &str,
+    layout: &Layout,
+    config: &Config,
+) -> Option<PathBuf> {
+    cache::lookup(&generated_marquee_path(system_name, system_folder, game_name, layout, config)?)
+}
+
+/// The folder ES scrapes a system's images to, inside its rom folder.
+pub fn images_path(system_folder: &str, config: &Config) -> PathBuf {
+    config.settings.roms_path.join(system_folder).join("images")
+}
+
+/// The placeholder values available to design sources and texts.
+pub fn design_values<'a>(
+    system_name: &'a str,
+    game_name: &'a str,
+    images_path: &'a Path,
+    config: &'a Config,
+) -> Vec<(&'static str, Value<'a>)> {
+    vec![
+        ("roms_path", Value::from(&config.settings.roms_path)),
+        ("images_path", Value::from(images_path)),
+        ("system_name", Value::from(system_name)),
+        ("game_name", Value::from(game_name)),
+    ]
+}
 
 pub fn autogen_marquee(
     system_name: &str,
+    system_folder: &str,
     game_name: &str,
+    layout: &Layout,
     config: &Config,
 ) -> Option<PathBuf> {
-    let fanart_path = find_fanart_file(system_name, game_name, config);
-    let logo_path = find_logo_file(system_name, game_name, config);
+    let target_path = generated_marquee_path(system_name, system_folder, game_name, layout, config)?;
+    if let Some(cached) = cache::lookup(&target_path) {
+        return Some(cached);
+    }
+    match write_marquee(system_name, system_folder, game_name, &target_path, layout, config) {
+        Ok(()) => Some(target_path),
+        Err(e) => {
+            error!("Failed to generate marquee {:?}: {}", target_path, e);
+            None
+        }
+    }
+}
+
+/// The files a game's marquee is drawn from, or `None` if its design lacks a required image.
+pub fn find_sources(system_name: &str, system_folder: &str, game_name: &str, config: &Config) -> Option<Vec<PathBuf>> {
+    let images_path = images_path(system_folder, config);
+    let values = design_values(system_name, game_name, &images_path, config);
+    config.designs.for_system(system_name).sources(&values)
+}
+
+pub fn write_marquee(
+    system_name: &str,
+    system_folder: &str,
+    game_name: &str,
+    target_path: &Path,
+    layout: &Layout,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let design = config.designs.for_system(system_name);
+    info!("Generating marquee for {}/{} with design '{}' and {:?}", system_name, game_name, design.name, layout);
+    let images_path = images_path(system_folder, config);
+    let values = design_values(system_name, game_name, &images_path, config);
+    let scene = Scene::load(design, &values, config)?;
+    match &design.animation {
+        None => cache::store(&scene.render(None, layout), target_path, config)?,
+        Some(animation) => {
+            cache::store_with(target_path, config, |path| write_animation(&scene, layout, animation, path, config))?
+        }
+    }
+    Ok(())
+}
+
+/// Draws the layers of `design` into a `MarqueeWidth`×`MarqueeHeight` image surrounded by a
+/// `MarqueeBorder` wide black border. Frame layers are drawn over the border, last.
+pub fn compose_marquee(
+    design: &Design,
+    values: &[(&str, Value)],
+    layout: &Layout,
+    config: &Config,
+) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    Ok(Scene::load(design, values, config)?.render(None, layout))
+}
+
+// The images, texts and palettes of a design, loaded once and drawn for every frame
+struct Scene<'a> {
+    design: &'a Design,
+    width: u32,
+    height: u32,
+    border: u32,
+    inner_width: u32,
+    inner_height: u32,
+    images: HashMap<&'a str, DynamicImage>,
+    texts: HashMap<&'a str, RgbaImage>,
+    frames: HashMap<&'a str, RgbaImage>,
+    palettes: HashMap<String, Palette>,
+}
+
+impl<'a> Scene<'a> {
+    fn load(design: &'a Design, values: &[(&str, Value)], config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let width = config.settings.marquee_width.max(1) as u32;
+        let height = config.settings.marquee_height.max(1) as u32;
+        let border = (config.settings.marquee_border.max(0) as u32).min(width.min(height) / 2);
+        let mut scene = Scene {
+            design,
+            width,
+            height,
+            border,
+            inner_width: (width - 2 * border).max(1),
+            inner_height: (height - 2 * border).max(1),
+            images: HashMap::new(),
+            texts: HashMap::new(),
+            frames: HashMap::new(),
+            palettes: HashMap::new(),
+        };
+
+        // Images are loaded first, so any layer can use the palette of any image
+        for layer in &design.layers {
+            if let LayerKind::Image { sources, required, .. } = &layer.kind {
+                match designer::first_existing(sources, values) {
+                    Some(path) => {
+                        let (box_width, box_height) = scene.box_size(layer);
+                        scene.images.insert(layer.name.as_str(), svg::open_image(&path, box_width, box_height)?);
+                    }
+                    None if *required => return Err(format!("No image found for layer '{}'", layer.name).into()),
+                    None => {}
+                }
+            }
+        }
+        scene.palettes = design
+            .palette_layers()
+            .filter_map(|name| scene.images.get(name).map(|image| (name.to_string(), Palette::extract(image))))
+            .collect();
+
+        for layer in &design.layers {
+            match &layer.kind {
+                LayerKind::Text { text, font, font_size, colour } => {
+                    let text = text.render(values)?;
+                    let font_size = (scene.inner_height as f32 * font_size).max(1.0);
+                    let (box_width, _) = scene.box_size(layer);
+                    let rendered = render_text(&text, font, font_size, colour.resolve(&scene.palettes), box_width)?;
+                    scene.texts.insert(layer.name.as_str(), rendered);
+                }
+                LayerKind::Frame { source, colour, thickness } => {
+                    let bezel = source
+                        .as_ref()
+                        .and_then(|source| designer::first_existing(std::slice::from_ref(source), values));
+                    let frame = match bezel {
+                        Some(path) => svg::open_image(&path, width, height)?
+                            .resize_exact(width, height, FilterType::Lanczos3)
+                            .to_rgba8(),
+                        None => solid_frame(width, height, *thickness, colour.resolve(&scene.palettes)),
+                    };
+                    scene.frames.insert(layer.name.as_str(), frame);
+                }
+                LayerKind::Image { .. } | LayerKind::Gradient { .. } => {}
+            }
+        }
+        Ok(scene)
+    }
+
+    fn box_size(&self, layer: &Layer) -> (u32, u32) {
+        (
+            ((self.inner_width as f32 * layer.width) as u32).max(1),
+            ((self.inner_height as f32 * layer.height) as u32).max(1),
+        )
+    }
+
+    /// Draws the scene, `time` seconds into the animation loop or as a still image.
+    fn render(&self, time: Option<f32>, layout: &Layout) -> RgbaImage {
+        let animation = self.design.animation.as_ref();
+        // Motions go there and back over the loop: 0 at the start and end, 1 halfway
+        let phase = match (time, animation) {
+            (Some(time), Some(animation)) => (1.0 - (2.0 * std::f32::consts::PI * time / animation.duration).cos()) / 2.0,
+            _ => 0.0,
+        };
+
+        let mut inner = RgbaImage::from_pixel(self.inner_width, self.inner_height, Rgba([0, 0, 0, 255]));
+        for layer in &self.design.layers {
+            let opacity = layer.opacity * fade(layer, time, animation);
+            let (box_width, box_height) = self.box_size(layer);
+            match &layer.kind {
+                LayerKind::Image { fit, .. } => {
+                    let Some(image) = self.images.get(layer.name.as_str()) else {
+                        continue;
+                    };
+                    let content = match fit {
+                        Fit::Cover if time.is_some() && layer.motion != Motion::None => {
+                            ken_burns(image, box_width, box_height, layer, phase, layout.fanart_offset)
+                        }
+                        Fit::Cover => cover(image, box_width, box_height, layout.fanart_offset),
+                        Fit::Contain => image.resize(box_width, box_height, FilterType::Lanczos3).to_rgba8(),
+                        Fit::Stretch => image.resize_exact(box_width, box_height, FilterType::Lanczos3).to_rgba8(),
+                    };
+                    place(&mut inner, content, layer, opacity, layout, &self.palettes);
+                }
+                LayerKind::Gradient { direction, colour, strength } => {
+                    let (x, y) = position(&inner, box_width, box_height, layer, layout);
+                    let area = (x, y, box_width, box_height);
+                    let gradient = direction.unwrap_or(layout.gradient);
+                    draw_gradient(&mut inner, area, gradient, colour.resolve(&self.palettes), strength * opacity);
+                }
+                LayerKind::Text { .. } => {
+                    if let Some(text) = self.texts.get(layer.name.as_str()) {
+                        place(&mut inner, text.clone(), layer, opacity, layout, &self.palettes);
+                    }
+                }
+                LayerKind::Frame { .. } => {}
+            }
+        }
 
-    if let (Some(fanart), Some(logo)) = (fanart_path, logo_path) {
-        let target_path = config.settings.marquee_image_path.join(format!("{}-{}-generated.png", system_name, game_name));
+        let mut canvas = RgbaImage::from_pixel(self.width, self.height, Rgba([0, 0, 0, 255]));
+        imageops::overlay(&mut canvas, &inner, self.border as i64, self.border as i64);
+        for layer in &self.design.layers {
+            if let Some(frame) = self.frames.get(layer.name.as_str()) {
+                let mut frame = frame.clone();
+                apply_opacity(&mut frame, layer.opacity * fade(layer, time, animation));
+                imageops::overlay(&mut canvas, &frame, 0, 0);
+            }
+        }
+        canvas
+    }
+}
 
-        let convert_command = config.settings.im_convert_command
-            .replace("{IMPath}", config.settings.im_path.to_str().unwrap_or(""))
-            .replace("{FanartPath}", fanart.to_str().unwrap_or(""))
-            .replace("{LogoPath}", logo.to_str().unwrap_or(""))
-            .replace("{ImgTargetPath}", target_path.to_str().unwrap_or(""));
+// The opacity factor of a layer fading in at the start of the loop and out at its end
+fn fade(layer: &Layer, time: Option<f32>, animation: Option<&Animation>) -> f32 {
+    match (time, animation) {
+        (Some(time), Some(animation)) if layer.fade > 0.0 => {
+            let remaining = animation.duration - time;
+            (time.min(remaining) / layer.fade).clamp(0.0, 1.0)
+        }
+        _ => 1.0,
+    }
+}
 
-        println!("Generating marquee with command: {}", convert_command);
+// A cover crop that zooms or pans with `phase` (0.0 to 1.0) by up to the layer's motion amount
+fn ken_burns(image: &DynamicImage, width: u32, height: u32, layer: &Layer, phase: f32, offset: f32) -> RgbaImage {
+    let (source_width, source_height) = image.dimensions();
+    let cover_scale = (width as f32 / source_width as f32).max(height as f32 / source_height as f32);
+    let amount = layer.motion_amount;
+    let zoom = match layer.motion {
+        Motion::ZoomIn => 1.0 + amount * phase,
+        Motion::ZoomOut => 1.0 + amount * (1.0 - phase),
+        Motion::PanLeft | Motion::PanRight | Motion::PanUp | Motion::PanDown => 1.0 + amount,
+        Motion::None => 1.0,
+    };
+    let crop_width = (width as f32 / (cover_scale * zoom)).min(source_width as f32);
+    let crop_height = (height as f32 / (cover_scale * zoom)).min(source_height as f32);
 
-        let mut cmd = Command::new("cmd");
-        cmd.arg("/C").arg(convert_command);
+    let offset = offset.clamp(0.0, 1.0);
+    let (along_x, along_y) = match layer.motion {
+        Motion::PanLeft => (1.0 - phase, offset),
+        Motion::PanRight => (phase, offset),
+        Motion::PanUp => (offset, 1.0 - phase),
+        Motion::PanDown => (offset, phase),
+        Motion::ZoomIn | Motion::ZoomOut | Motion::None => (0.5, offset),
+    };
+    let x = ((source_width as f32 - crop_width) * along_x) as u32;
+    let y = ((source_height as f32 - crop_height) * along_y) as u32;
+    image
+        .crop_imm(x, y, (crop_width as u32).max(1), (crop_height as u32).max(1))
+        .resize_exact(width, height, FilterType::Triangle)
+        .to_rgba8()
+}
 
-        if cmd.status().is_ok() {
-            return Some(target_path);
+// Writes the frames as a looping GIF, or as a PNG sequence handed to `GeneratorEncoderCommand`
+// Frames are encoded as they are rendered, so only one is in memory at a time
+fn write_animation(
+    scene: &Scene,
+    layout: &Layout,
+    animation: &Animation,
+    target_path: &Path,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let frames = (0..animation.frame_count())
+        .map(|frame| scene.render(Some(frame as f32 / animation.frame_rate as f32), layout));
+    match animation.format {
+        AnimationFormat::Gif => {
+            let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(target_path)?), GIF_ENCODER_SPEED);
+            encoder.set_repeat(Repeat::Infinite)?;
+            let delay = Delay::from_numer_denom_ms(1000, animation.frame_rate);
+            for frame in frames {
+                encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay))?;
+            }
+        }
+        AnimationFormat::Mp4 => {
+            let frames_dir = target_path.with_extension("frames");
+            fs::create_dir_all(&frames_dir)?;
+            for (index, frame) in frames.enumerate() {
+                if let Err(e) = frame.save(frames_dir.join(format!("frame{:04}.png", index))) {
+                    let _ = fs::remove_dir_all(&frames_dir);
+                    return Err(e.into());
+                }
+            }
+            let frame_rate = animation.frame_rate.to_string();
+            let frames_pattern = frames_dir.join("frame%04d.png");
+            let command_line = config.templates.encoder.render(&[
+                ("frames", Value::from(&frames_pattern)),
+                ("frame_rate", Value::from(frame_rate.as_str())),
+                ("output", Value::from(target_path)),
+            ])?;
+            info!("Encoding animated marquee: {}", command_line);
+            let status = process::shell_command(&command_line).status();
+            let _ = fs::remove_dir_all(&frames_dir);
+            if !status?.success() || !target_path.exists() {
+                return Err(format!("GeneratorEncoderCommand failed to write {:?}", target_path).into());
+            }
         }
     }
-    None
+    Ok(())
 }
 
-fn find_fanart_file(system_name: &str, game_name: &str, config: &Config) -> Option<PathBuf> {
-    let fanart_path = config.settings.roms_path.join(system_name).join("images").join(format!("{}-fanart.jpg", game_name));
-    if fanart_path.exists() {
-        return Some(fanart_path);
+// Where content of the given size goes in `area`, following the layer anchor and offsets
+fn position(area: &RgbaImage, width: u32, height: u32, layer: &Layer, layout: &Layout) -> (u32, u32) {
+    let (mut align_x, align_y) = layer.anchor.alignment();
+    let mut offset_x = layer.offset_x;
+    if layer.anchor == Anchor::Layout {
+        match layout.logo_anchor {
+            LogoAnchor::Left => align_x = 0.0,
+            // A centered logo ignores the horizontal offset meant as a margin
+            LogoAnchor::Center => offset_x = 0.0,
+        }
     }
-    None
+    let x = area.width().saturating_sub(width) as f32 * align_x + area.width() as f32 * offset_x;
+    let y = area.height().saturating_sub(height) as f32 * align_y + area.height() as f32 * layer.offset_y;
+    (x.max(0.0) as u32, y.max(0.0) as u32)
 }
 
-fn find_logo_file(system_name: &str, game_name: &str, config: &Config) -> Option<PathBuf> {
-    let logo_path = config.settings.roms_path.join(system_name).join("images").join(format!("{}-marquee.png", game_name));
-    if logo_path.exists() {
-        return Some(logo_path);
+fn place(
+    area: &mut RgbaImage,
+    mut content: RgbaImage,
+    layer: &Layer,
+    opacity: f32,
+    layout: &Layout,
+    palettes: &HashMap<String, Palette>,
+) {
+    apply_opacity(&mut content, opacity);
+    let (x, y) = position(area, content.width(), content.height(), layer, layout);
+    if let Some(shadow) = &layer.shadow {
+        let (shadow_image, padding) = drop_shadow(&content, shadow.colour.resolve(palettes), shadow.blur);
+        let offset = shadow.offset as i64 - padding as i64;
+        imageops::overlay(area, &shadow_image, x as i64 + offset, y as i64 + offset);
+    }
+    imageops::overlay(area, &content, x as i64, y as i64);
+}
+
+fn apply_opacity(image: &mut RgbaImage, opacity: f32) {
+    if opacity >= 1.0 {
+        return;
+    }
+    for pixel in image.pixels_mut() {
+        pixel.0[3] = (pixel.0[3] as f32 * opacity) as u8;
+    }
+}
+
+// The silhouette of `content` in the shadow colour, blurred, with the padding added for the blur
+fn drop_shadow(content: &RgbaImage, colour: Rgba<u8>, blur: f32) -> (RgbaImage, u32) {
+    let padding = (blur * 3.0).ceil() as u32;
+    let [red, green, blue, alpha] = colour.0;
+    let mut image = RgbaImage::from_pixel(
+        content.width() + 2 * padding,
+        content.height() + 2 * padding,
+        Rgba([red, green, blue, 0]),
+    );
+    for (x, y, pixel) in content.enumerate_pixels() {
+        let shadow_alpha = (pixel.0[3] as u32 * alpha as u32 / 255) as u8;
+        image.put_pixel(x + padding, y + padding, Rgba([red, green, blue, shadow_alpha]));
+    }
+    if blur > 0.0 {
+        image = imageops::blur(&image, blur);
+    }
+    (image, padding)
+}
+
+fn solid_frame(width: u32, height: u32, thickness: u32, colour: Rgba<u8>) -> RgbaImage {
+    let mut frame = RgbaImage::new(width, height);
+    for (x, y, pixel) in frame.enumerate_pixels_mut() {
+        if x < thickness || y < thickness || x + thickness >= width || y + thickness >= height {
+            *pixel = colour;
+        }
+    }
+    frame
+}
+
+/// Renders a single line of text, shrunk to fit `max_width` if needed.
+fn render_text(
+    text: &str,
+    font_path: &Path,
+    font_size: f32,
+    colour: Rgba<u8>,
+    max_width: u32,
+) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    let font = FontVec::try_from_vec(fs::read(font_path)?)
+        .map_err(|e| format!("Failed to load font {:?}: {}", font_path, e))?;
+    Ok(draw_line(&font, text, font_size, colour, max_width))
+}
+
+/// Renders a single line of text with `font`, shrunk to fit `max_width` if needed.
+pub fn draw_line(font: &FontVec, text: &str, font_size: f32, colour: Rgba<u8>, max_width: u32) -> RgbaImage {
+    let mut scale = PxScale::from(font_size);
+    let text_width = measure_text(font, scale, text);
+    if text_width > max_width as f32 {
+        scale = PxScale::from(font_size * max_width as f32 / text_width);
+    }
+    let scaled = font.as_scaled(scale);
+    let width = (measure_text(font, scale, text).ceil() as u32).max(1);
+    let height = ((scaled.ascent() - scaled.descent()).ceil() as u32).max(1);
+
+    let [red, green, blue, alpha] = colour.0;
+    let mut image = RgbaImage::from_pixel(width, height, Rgba([red, green, blue, 0]));
+    let mut caret = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph_id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, glyph_id);
+        }
+        let glyph = glyph_id.with_scale_and_position(scale, point(caret, scaled.ascent()));
+        caret += scaled.h_advance(glyph_id);
+        previous = Some(glyph_id);
+
+        if let Some(outline) = font.outline_glyph(glyph) {
+            let bounds = outline.px_bounds();
+            outline.draw(|x, y, coverage| {
+                let x = x as i32 + bounds.min.x as i32;
+                let y = y as i32 + bounds.min.y as i32;
+                if x >= 0 && y >= 0 && (x as u32) < width && (y as u32) < height {
+                    let pixel = image.get_pixel_mut(x as u32, y as u32);
+                    let glyph_alpha = (coverage * alpha as f32) as u8;
+                    pixel.0[3] = pixel.0[3].max(glyph_alpha);
+                }
+            });
+        }
+    }
+    image
+}
+
+fn measure_text(font: &FontVec, scale: PxScale, text: &str) -> f32 {
+    let scaled = font.as_scaled(scale);
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph_id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, glyph_id);
+        }
+        width += scaled.h_advance(glyph_id);
+        previous = Some(glyph_id);
+    }
+    width
+}
+
+/// Scales `image` to cover `width`×`height` and crops the overflow. `offset` picks which part
+/// of the vertical (or horizontal) overflow is kept.
+fn cover(image: &DynamicImage, width: u32, height: u32, offset: f32) -> RgbaImage {
+    let (source_width, source_height) = image.dimensions();
+    let scale = (width as f32 / source_width as f32).max(height as f32 / source_height as f32);
+    let scaled_width = ((source_width as f32 * scale).ceil() as u32).max(width);
+    let scaled_height = ((source_height as f32 * scale).ceil() as u32).max(height);
+    let scaled = image.resize_exact(scaled_width, scaled_height, FilterType::Lanczos3);
+
+    let offset = offset.clamp(0.0, 1.0);
+    let x = ((scaled_width - width) as f32 * offset) as u32;
+    let y = ((scaled_height - height) as f32 * offset) as u32;
+    scaled.crop_imm(x, y, width, height).to_rgba8()
+}
+
+// Blends `colour` over `area` (x, y, width, height), strongest on the gradient's dark side
+fn draw_gradient(image: &mut RgbaImage, area: (u32, u32, u32, u32), gradient: Gradient, colour: Rgba<u8>, strength: f32) {
+    if gradient == Gradient::None {
+        return;
+    }
+    let (left, top, width, height) = area;
+    let colour_alpha = colour.0[3] as f32 / 255.0;
+    for y in top..(top + height).min(image.height()) {
+        for x in left..(left + width).min(image.width()) {
+            let (rx, ry) = ((x - left) as f32 / width as f32, (y - top) as f32 / height as f32);
+            let position = match gradient {
+                Gradient::None => 0.0,
+                Gradient::Left => 1.0 - rx,
+                Gradient::Right => rx,
+                Gradient::Bottom => ry,
+                Gradient::Full => FULL_GRADIENT_STRENGTH,
+            };
+            let blend = position * strength * colour_alpha;
+            let pixel = image.get_pixel_mut(x, y);
+            for (channel, target) in pixel.0.iter_mut().zip(colour.0).take(3) {
+                *channel = (*channel as f32 * (1.0 - blend) + target as f32 * blend) as u8;
+            }
+        }
     }
-    None
 }
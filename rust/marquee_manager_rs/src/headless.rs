@@ -0,0 +1,200 @@
+use crate::config::Config;
+use crate::display::{Display, TextStyle};
+use crate::generator;
+use crate::media::MediaKind;
+use crate::profiles::Scale;
+use crate::svg;
+use ab_glyph::FontVec;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use log::{error, info, warn};
+use resvg::usvg::fontdb;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// mpv sizes OSD text for a 720 pixel high window and scales it with the window
+const OSD_REFERENCE_HEIGHT: f32 = 720.0;
+const TEXT_MARGIN: u32 = 20;
+const TEXT_SHADOW_OFFSET: i64 = 3;
+
+#[derive(Serialize, Default, Clone)]
+struct Status {
+    output: String,
+    width: u32,
+    height: u32,
+    media: Option<PathBuf>,
+    // False when the media could not be decoded (videos) and the frame is left blank
+    media_rendered: bool,
+    text: Option<String>,
+    text_expires_at: Option<u64>,
+    text_font_size: Option<i32>,
+    text_align: Option<String>,
+    updated_at: u64,
+    // The media as last shown, which texts are drawn over
+    #[serde(skip)]
+    media_frame: Option<RgbaImage>,
+}
+
+/// A display without a window: every change is composed into `<name>.png` and described in
+/// `<name>.json` inside the output directory, for testing, streaming overlays or remote preview.
+pub struct HeadlessDisplay {
+    frame_path: PathBuf,
+    status_path: PathBuf,
+    status: Mutex<Status>,
+}
+
+impl HeadlessDisplay {
+    pub fn new(name: &str, width: i32, height: i32, directory: &Path) -> Self {
+        if let Err(e) = fs::create_dir_all(directory) {
+            error!("Failed to create headless output directory {:?}: {}", directory, e);
+        }
+        HeadlessDisplay {
+            frame_path: directory.join(format!("{}.png", name)),
+            status_path: directory.join(format!("{}.json", name)),
+            status: Mutex::new(Status {
+                output: name.to_string(),
+                width: width.max(1) as u32,
+                height: height.max(1) as u32,
+                ..Status::default()
+            }),
+        }
+    }
+
+    fn write(&self, status: &mut Status, frame: &RgbaImage) {
+        status.updated_at = unix_now();
+        if let Err(e) = frame.save(&self.frame_path) {
+            error!("Failed to write headless frame {:?}: {}", self.frame_path, e);
+        }
+        match serde_json::to_string_pretty(&*status) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.status_path, json) {
+                    error!("Failed to write headless status {:?}: {}", self.status_path, e);
+                }
+            }
+            Err(e) => error!("Failed to serialize headless status: {}", e),
+        }
+    }
+}
+
+impl Display for HeadlessDisplay {
+    fn show_media(&self, path: &Path, config: &Config) {
+        let mut status = self.status.lock().unwrap();
+        let mut frame = blank_frame(status.width, status.height);
+        status.media = Some(path.to_path_buf());
+        // Videos are only recorded, there is no frame to render without a player
+        if MediaKind::of(path).is_some_and(|kind| !kind.capabilities().decodable) {
+            status.media_rendered = false;
+            status.media_frame = None;
+            self.write(&mut status, &frame);
+            return;
+        }
+        status.media_rendered = match image::open(path) {
+            Ok(media) => {
+                let (width, height) = (status.width, status.height);
+                let fitted = match config.profiles.for_file(path).scale {
+                    Scale::Fit => media.resize(width, height, FilterType::Triangle),
+                    Scale::Fill => media.resize_to_fill(width, height, FilterType::Triangle),
+                    Scale::Stretch => media.resize_exact(width, height, FilterType::Triangle),
+                }
+                .to_rgba8();
+                let x = (status.width - fitted.width()) / 2;
+                let y = (status.height - fitted.height()) / 2;
+                imageops::overlay(&mut frame, &fitted, x as i64, y as i64);
+                true
+            }
+            Err(e) => {
+                info!("Headless output cannot render {:?}: {}", path, e);
+                false
+            }
+        };
+        status.media_frame = Some(frame.clone());
+        self.write(&mut status, &frame);
+    }
+
+    // The text stays on the frame until the next media, text_expires_at tells when mpv would
+    // have removed it
+    fn show_text(&self, text: &str, style: &TextStyle, _config: &Config) {
+        let mut status = self.status.lock().unwrap();
+        status.text = Some(text.to_string());
+        status.text_expires_at = Some(unix_now() + style.duration.as_secs());
+        status.text_font_size = Some(style.font_size);
+        status.text_align = Some(format!("{}-{}", style.align_y, style.align_x));
+        let mut frame = status.media_frame.clone().unwrap_or_else(|| blank_frame(status.width, status.height));
+        match osd_font() {
+            Some(font) => draw_text(&mut frame, font, text, style),
+            None => warn!("No sans-serif font installed, the headless frame is written without its text"),
+        }
+        self.write(&mut status, &frame);
+    }
+
+    fn clear(&self, _config: &Config) {
+        let mut status = self.status.lock().unwrap();
+        status.media = None;
+        status.media_rendered = false;
+        status.text = None;
+        status.text_expires_at = None;
+        status.text_font_size = None;
+        status.text_align = None;
+        status.media_frame = None;
+        let frame = blank_frame(status.width, status.height);
+        self.write(&mut status, &frame);
+    }
+}
+
+// The system's sans-serif font, loaded once, standing in for mpv's OSD font
+fn osd_font() -> Option<&'static FontVec> {
+    static FONT: OnceLock<Option<FontVec>> = OnceLock::new();
+    FONT.get_or_init(|| {
+        let fonts = svg::system_fonts();
+        let query = fontdb::Query { families: &[fontdb::Family::SansSerif], ..fontdb::Query::default() };
+        let id = fonts.query(&query)?;
+        fonts.with_face_data(id, |data, index| FontVec::try_from_vec_and_index(data.to_vec(), index).ok())?
+    })
+    .as_ref()
+}
+
+// Draws white lines of text with a drop shadow, placed like mpv places its OSD
+fn draw_text(frame: &mut RgbaImage, font: &FontVec, text: &str, style: &TextStyle) {
+    let (width, height) = frame.dimensions();
+    let font_size = (style.font_size.max(1) as f32 * height as f32 / OSD_REFERENCE_HEIGHT).max(1.0);
+    let max_width = width.saturating_sub(2 * TEXT_MARGIN).max(1);
+    let lines: Vec<(RgbaImage, RgbaImage)> = text
+        .lines()
+        .map(|line| {
+            let text = generator::draw_line(font, line, font_size, Rgba([255, 255, 255, 255]), max_width);
+            let shadow = generator::draw_line(font, line, font_size, Rgba([0, 0, 0, 192]), max_width);
+            (text, shadow)
+        })
+        .collect();
+
+    let block_height: u32 = lines.iter().map(|(line, _)| line.height()).sum();
+    let mut y = match style.align_y.as_str() {
+        "top" => TEXT_MARGIN as i64,
+        "bottom" => height as i64 - TEXT_MARGIN as i64 - block_height as i64,
+        _ => (height as i64 - block_height as i64) / 2,
+    };
+    for (line, shadow) in &lines {
+        let x = match style.align_x.as_str() {
+            "left" => TEXT_MARGIN as i64,
+            "right" => width as i64 - TEXT_MARGIN as i64 - line.width() as i64,
+            _ => (width as i64 - line.width() as i64) / 2,
+        };
+        imageops::overlay(frame, shadow, x + TEXT_SHADOW_OFFSET, y + TEXT_SHADOW_OFFSET);
+        imageops::overlay(frame, line, x, y);
+        y += line.height() as i64;
+    }
+}
+
+fn blank_frame(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,48 @@
+use crate::generator::Layout;
+use log::error;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Generated marquee layouts chosen with the F6–F11 keys, saved per game.
+pub struct LayoutStore {
+    path: PathBuf,
+    layouts: HashMap<String, Layout>,
+}
+
+impl LayoutStore {
+    pub fn load(path: &Path) -> Self {
+        let layouts = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                error!("Failed to parse saved layouts {:?}: {}", path, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        LayoutStore {
+            path: path.to_path_buf(),
+            layouts,
+        }
+    }
+
+    /// The saved layout for a game, or `default` if none was chosen yet.
+    pub fn get(&self, system_name: &str, game_name: &str, default: &Layout) -> Layout {
+        self.layouts.get(&key(system_name, game_name)).copied().unwrap_or(*default)
+    }
+
+    pub fn set(&mut self, system_name: &str, game_name: &str, layout: Layout) {
+        self.layouts.insert(key(system_name, game_name), layout);
+        match serde_json::to_string_pretty(&self.layouts) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.path, json) {
+                    error!("Failed to save layouts to {:?}: {}", self.path, e);
+                }
+            }
+            Err(e) => error!("Failed to serialize layouts: {}", e),
+        }
+    }
+}
+
+fn key(system_name: &str, game_name: &str) -> String {
+    format!("{}/{}", system_name, game_name)
+}
@@ -1,11 +1,13 @@
 use simplelog::{Config, LevelFilter, WriteLogger};
 use std::fs::File;
+use std::path::Path;
 
-pub fn init() {
+/// Logs to `marquee_manager_rs.log` in `directory`.
+pub fn init(directory: &Path) {
     WriteLogger::init(
         LevelFilter::Info,
         Config::default(),
-        File::create("marquee_manager_rs.log").unwrap(),
+        File::create(directory.join("marquee_manager_rs.log")).unwrap(),
     )
     .expect("Failed to initialize logger");
 }
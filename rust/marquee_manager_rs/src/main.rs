@@ -1,29 +1,116 @@
+mod batch;
+mod cache;
+mod colour;
 mod config;
+mod convert;
+mod designer;
+mod display;
 mod events;
+mod fallback;
+mod gamelist;
 mod generator;
+mod headless;
 mod keyboard;
+mod layouts;
 mod logger;
 mod marquee;
+mod media;
+mod messages;
+mod migrate;
+mod outputs;
+mod overlay;
+mod palette;
 mod process;
-mod registry;
+mod profiles;
+mod reload;
 mod state;
-mod systems;
+mod svg;
+mod template;
+mod validate;
 
 use crate::config::Config;
+use clap::{Parser, Subcommand};
+use crate::display::TextStyle;
+use crate::generator::{Layout, FANART_OFFSET_STEP};
+use crate::layouts::LayoutStore;
+use crate::outputs::OutputManager;
+use crate::reload::{SharedConfig, SharedSystems};
 use crate::state::AppState;
-use log::{error, info, warn};
-use std::path::{Path, PathBuf};
-use std::sync::{mpsc::channel, Arc, Mutex};
+use marquee_core::systems;
+use log::{error, info};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{mpsc::channel, Arc, Mutex, RwLock};
 use std::thread;
 use std::time::Duration;
 
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(
+        long,
+        global = true,
+        env = "MARQUEE_MANAGER_CONFIG",
+        help = "Configuration file, defaults to config.ini next to the executable"
+    )]
+    config: Option<PathBuf>,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    #[clap(about = "Generate the marquee of every game with fanart and a logo but no marquee")]
+    GenerateAll {
+        #[clap(long, help = "Number of worker threads, defaults to the number of CPUs")]
+        jobs: Option<usize>,
+        #[clap(long, help = "Regenerate marquees that are already up to date")]
+        force: bool,
+    },
+    #[clap(about = "Check config.ini and list every problem found, exiting with 1 on errors")]
+    CheckConfig,
+    #[clap(about = "Update an older config.ini to the current keys, keeping a backup of the original")]
+    MigrateConfig {
+        #[clap(long, help = "List the changes without writing the file")]
+        dry_run: bool,
+    },
+    #[clap(about = "Manage the cache of generated and converted marquees")]
+    Cache {
+        #[clap(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    #[clap(about = "Delete every cached marquee")]
+    Purge,
+    #[clap(about = "Delete every cached marquee and generate them again")]
+    Rebuild {
+        #[clap(long, help = "Number of worker threads, defaults to the number of CPUs")]
+        jobs: Option<usize>,
+    },
+}
+
 fn main() {
-    logger::init();
+    let cli = Cli::parse();
+    // Nothing depends on the working directory, so shortcuts and schedulers can start us anywhere
+    logger::init(&marquee_core::config::executable_dir());
     info!("Marquee Manager starting...");
 
     // Load configurations
-    let config_path = Path::new("config.ini");
-    let mut config = match Config::load_config(config_path) {
+    let config_path = marquee_core::config::locate_config(cli.config.as_deref());
+    info!("Using configuration {:?}", config_path);
+    if let Some(Commands::CheckConfig) = cli.command {
+        let report = validate::check_file(&config_path);
+        println!("{}", report.describe(&messages::catalog_for_file(&config_path)));
+        std::process::exit(if report.has_errors() { 1 } else { 0 });
+    }
+    if let Some(Commands::MigrateConfig { dry_run }) = cli.command {
+        migrate_config(&config_path, dry_run);
+        return;
+    }
+    let config = match Config::load_config(&config_path) {
         Ok(c) => {
             info!("Config loaded successfully.");
             c
@@ -34,22 +121,22 @@ fn main() {
         }
     };
 
-    // --- RetroBat Path Logic ---
+    // RetroBatPath falls back to the registry while loading the configuration
     if config.settings.retrobat_path.is_none() {
-        warn!("RetroBatPath not found in config.ini. Attempting to read from Windows Registry...");
-        if let Some(path_str) = registry::get_retrobat_path() {
-            info!("Found RetroBat path in registry: {}", path_str);
-            config.settings.retrobat_path = Some(PathBuf::from(path_str));
-        } else {
-            error!("Could not find RetroBat installation path in config or registry. Exiting.");
-            return;
-        }
+        error!("Could not find RetroBat installation path in config or registry. Exiting.");
+        return;
     }
-    // --- End RetroBat Path Logic ---
 
+    let mut report = validate::Report::default();
+    validate::check_config(&config, &mut report);
+    report.log(&config.messages);
+    // Like a reload, a configuration with errors is refused rather than half applied
+    if report.has_errors() {
+        error!("Invalid configuration, run check-config for details. Exiting.");
+        return;
+    }
 
-    let systems_path = Path::new("."); // In a real scenario, this would come from the config
-    let systems = match systems::load_all_systems_configs(systems_path) {
+    let systems = match systems::load_all_systems_configs(&config.settings.es_systems_path) {
         Ok(s) => {
             info!("Systems loaded successfully.");
             s
@@ -60,23 +147,67 @@ fn main() {
         }
     };
 
+    match cli.command {
+        Some(Commands::GenerateAll { jobs, force }) => {
+            generate_all(&config, &systems, jobs, force);
+            return;
+        }
+        Some(Commands::Cache { action: CacheAction::Purge }) => {
+            println!("{}", config.messages.format("cache_purged", &[("count", &cache::purge(&config))]));
+            return;
+        }
+        Some(Commands::Cache { action: CacheAction::Rebuild { jobs } }) => {
+            println!("{}", config.messages.format("cache_purged", &[("count", &cache::purge(&config))]));
+            generate_all(&config, &systems, jobs, false);
+            return;
+        }
+        Some(Commands::CheckConfig) | Some(Commands::MigrateConfig { .. }) | None => {}
+    }
+
     // Create a shared state
     let app_state = Arc::new(Mutex::new(AppState::default()));
 
-    // Launch the media player
-    process::launch_media_player(&config);
+    // Launch one media player per output and keep them running
+    let outputs = Arc::new(OutputManager::new(config.outputs.clone()));
+    outputs.launch_all(&config);
+    let shared_config: SharedConfig = Arc::new(RwLock::new(Arc::new(config)));
+    let shared_systems: SharedSystems = Arc::new(RwLock::new(Arc::new(systems)));
+    let supervisor_config = shared_config.clone();
+    let supervisor_outputs = outputs.clone();
+    let _supervisor_thread = thread::spawn(move || {
+        supervisor_outputs.supervise(&supervisor_config);
+    });
+
+    // Layouts chosen with F6-F11, also read by the event watcher to find generated marquees
+    let layouts = Arc::new(Mutex::new(LayoutStore::load(
+        &reload::current(&shared_config).settings.generator_layouts_path,
+    )));
 
     // Start the event watcher in a new thread
-    let event_config = config.clone();
-    let event_systems = systems.clone();
+    let event_config = shared_config.clone();
+    let event_systems = shared_systems.clone();
     let event_state = app_state.clone();
+    let event_outputs = outputs.clone();
+    let event_layouts = layouts.clone();
     let _event_thread = thread::spawn(move || {
-        if let Err(e) = events::start_watching(&event_config, &event_systems, event_state) {
+        if let Err(e) =
+            events::start_watching(&event_config, &event_systems, event_state, event_outputs, event_layouts)
+        {
             error!("Error in event watcher: {}", e);
         }
     });
     info!("Event watcher started.");
 
+    // Apply changes to config.ini without restarting
+    let reload_config = shared_config.clone();
+    let reload_systems = shared_systems.clone();
+    let reload_outputs = outputs.clone();
+    let _reload_thread = thread::spawn(move || {
+        if let Err(e) = reload::watch_config(&config_path, reload_config, reload_systems, reload_outputs) {
+            error!("Error in config watcher: {}", e);
+        }
+    });
+
     // Start the keyboard listener in a new thread
     let (tx, rx) = channel();
     let _keyboard_thread = thread::spawn(move || {
@@ -89,28 +220,62 @@ fn main() {
     // Main application loop
     loop {
         if let Ok(key_event) = rx.try_recv() {
+            let config = reload::current(&shared_config);
+            let systems = reload::current(&shared_systems);
             match key_event {
-                keyboard::KeyboardEvent::F6 => info!("F6 pressed: Cycle gradient"),
+                keyboard::KeyboardEvent::F6 => {
+                    info!("F6 pressed: Cycle gradient");
+                    adjust_layout(&app_state, &layouts, &outputs, &systems, &config, |layout| {
+                        layout.gradient = layout.gradient.next()
+                    });
+                }
                 keyboard::KeyboardEvent::F7 => {
                     info!("F7 pressed, attempting to generate marquee...");
                     let state = app_state.lock().unwrap();
                     if let Some((system, game)) = &state.current_game {
+                        let config = config.for_system(system);
+                        let text = config.messages.get("generating_marquee");
+                        outputs.show_text(text, &TextStyle::message(Duration::from_secs(2)), config);
+                        let layout = layouts.lock().unwrap().get(system, game, &config.layout);
+                        let system_folder = systems::folder(&systems, system);
                         if let Some(generated_marquee) =
-                            generator::autogen_marquee(system, game, &config)
+                            generator::autogen_marquee(system, system_folder, game, &layout, config)
                         {
                             info!("Generated marquee: {:?}", generated_marquee);
-                            process::update_marquee(&generated_marquee, &config);
+                            outputs.show_marquee(&generated_marquee, config);
                         } else {
-                            error!("Could not generate marquee: fanart or logo missing.");
+                            error!("Could not generate marquee: artwork missing.");
+                            let text = config.messages.get("generation_failed");
+                            outputs.show_text(text, &TextStyle::message(Duration::from_secs(3)), config);
                         }
                     } else {
                         info!("No game selected, cannot generate marquee.");
                     }
                 }
-                keyboard::KeyboardEvent::F8 => info!("F8 pressed: Adjust fanart vertical alignment"),
-                keyboard::KeyboardEvent::F9 => info!("F9 pressed: Cycle fanart vertical alignment"),
-                keyboard::KeyboardEvent::F10 => info!("F10 pressed: Align logo left"),
-                keyboard::KeyboardEvent::F11 => info!("F11 pressed: Align logo center"),
+                keyboard::KeyboardEvent::F8 => {
+                    info!("F8 pressed: Move fanart alignment up");
+                    adjust_layout(&app_state, &layouts, &outputs, &systems, &config, |layout| {
+                        layout.fanart_offset = (layout.fanart_offset - FANART_OFFSET_STEP).max(0.0)
+                    });
+                }
+                keyboard::KeyboardEvent::F9 => {
+                    info!("F9 pressed: Move fanart alignment down");
+                    adjust_layout(&app_state, &layouts, &outputs, &systems, &config, |layout| {
+                        layout.fanart_offset = (layout.fanart_offset + FANART_OFFSET_STEP).min(1.0)
+                    });
+                }
+                keyboard::KeyboardEvent::F10 => {
+                    info!("F10 pressed: Align logo left");
+                    adjust_layout(&app_state, &layouts, &outputs, &systems, &config, |layout| {
+                        layout.logo_anchor = generator::LogoAnchor::Left
+                    });
+                }
+                keyboard::KeyboardEvent::F11 => {
+                    info!("F11 pressed: Align logo center");
+                    adjust_layout(&app_state, &layouts, &outputs, &systems, &config, |layout| {
+                        layout.logo_anchor = generator::LogoAnchor::Center
+                    });
+                }
                 keyboard::KeyboardEvent::F12 => {
                     info!("F12 pressed, exiting...");
                     break;
@@ -121,6 +286,87 @@ fn main() {
     }
 
     // Clean up
-    process::kill_media_player(&config);
+    outputs.shutdown();
     info!("Marquee Manager has shut down.");
 }
+
+fn migrate_config(config_path: &std::path::Path, dry_run: bool) {
+    let messages = messages::catalog_for_file(config_path);
+    let path = config_path.display();
+    let migration = match migrate::migrate_file(config_path, dry_run) {
+        Ok(migration) => migration,
+        Err(e) => {
+            eprintln!("{}", messages.format("migrate_failed", &[("path", &path), ("error", &e)]));
+            std::process::exit(1);
+        }
+    };
+    if migration.from_version == migrate::CURRENT_VERSION {
+        let version = migrate::CURRENT_VERSION;
+        println!("{}", messages.format("migrate_up_to_date", &[("path", &path), ("version", &version)]));
+        return;
+    }
+    for change in &migration.changes {
+        println!("{}", change.describe(&messages));
+    }
+    if !migration.report.issues.is_empty() {
+        println!("{}", migration.report.describe(&messages));
+    }
+    let (from, to) = (migration.from_version, migrate::CURRENT_VERSION);
+    if migration.report.has_errors() {
+        eprintln!("{}", messages.format("migrate_invalid", &[("path", &path), ("from", &from), ("to", &to)]));
+        std::process::exit(1);
+    }
+    match migration.backup {
+        Some(backup) => {
+            let args: &[(&str, &dyn std::fmt::Display)] =
+                &[("path", &path), ("from", &from), ("to", &to), ("backup", &backup.display())];
+            println!("{}", messages.format("migrate_done", args));
+        }
+        None => println!("{}", messages.format("migrate_dry_run", &[("path", &path), ("from", &from), ("to", &to)])),
+    }
+}
+
+fn generate_all(config: &Config, systems: &HashMap<String, String>, jobs: Option<usize>, force: bool) {
+    let jobs = jobs.unwrap_or_else(|| thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+    let layouts = LayoutStore::load(&config.settings.generator_layouts_path);
+    let summary = batch::generate_all(config, systems, &layouts, jobs, force);
+    let summary_line = config.messages.format(
+        "batch_summary",
+        &[("generated", &summary.generated), ("skipped", &summary.skipped), ("failed", &summary.failed.len())],
+    );
+    println!("{}", summary_line);
+    for failure in &summary.failed {
+        println!("  {}", failure);
+    }
+}
+
+// Applies a layout change to the current game, saves it and re-renders the generated marquee
+fn adjust_layout(
+    app_state: &Arc<Mutex<AppState>>,
+    layouts: &Mutex<LayoutStore>,
+    outputs: &OutputManager,
+    systems: &HashMap<String, String>,
+    config: &Config,
+    adjust: impl FnOnce(&mut Layout),
+) {
+    let state = app_state.lock().unwrap();
+    let (system, game) = match &state.current_game {
+        Some(current_game) => current_game,
+        None => {
+            info!("No game selected, cannot adjust the marquee layout.");
+            return;
+        }
+    };
+
+    let config = config.for_system(system);
+    let mut layouts = layouts.lock().unwrap();
+    let mut layout = layouts.get(system, game, &config.layout);
+    adjust(&mut layout);
+    layouts.set(system, game, layout);
+    drop(layouts);
+
+    match generator::autogen_marquee(system, systems::folder(systems, system), game, &layout, config) {
+        Some(generated_marquee) => outputs.show_marquee(&generated_marquee, config),
+        None => error!("Could not regenerate marquee: artwork missing."),
+    }
+}
@@ -1,6 +1,14 @@
 use crate::config::Config;
+use crate::convert;
+use crate::fallback;
+use crate::generator::{self, Layout};
+use crate::media::MediaKind;
+use crate::svg;
+use crate::template::{TemplateError, Value};
+use log::error;
+use marquee_core::systems;
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 pub enum MarqueeType<'a> {
     System {
@@ -9,6 +17,10 @@ pub enum MarqueeType<'a> {
     Game {
         system_name: &'a str,
         game_name: &'a str,
+        /// The name from gamelist.xml, used by the text fallback.
+        display_name: Option<&'a str>,
+        /// The generated marquee layout chosen for the game.
+        layout: Layout,
     },
     Collection {
         collection_name: &'a str,
@@ -20,17 +32,25 @@ pub fn find_marquee_file(
     config: &Config,
     systems: &HashMap<String, String>,
 ) -> PathBuf {
-    match marquee_type {
+    let found = match marquee_type {
         MarqueeType::System { system_name } => {
             find_system_marquee(system_name, config, systems).unwrap_or_else(|| config.settings.default_image_path.clone())
         }
-        MarqueeType::Game { system_name, game_name } => {
-            find_game_marquee(system_name, game_name, config, systems).unwrap_or_else(|| config.settings.default_image_path.clone())
+        MarqueeType::Game { system_name, game_name, display_name, layout } => {
+            find_game_marquee(system_name, game_name, display_name, &layout, config, systems).unwrap_or_else(|| config.settings.default_image_path.clone())
         }
         MarqueeType::Collection { collection_name } => {
             find_collection_marquee(collection_name, config).unwrap_or_else(|| config.settings.default_image_path.clone())
         }
+    };
+    if MediaKind::of(&found).is_some_and(|kind| kind.capabilities().rasterized) {
+        // Rasters are already at the marquee geometry, they never need converting
+        if let Some(raster) = svg::rasterize_for_display(&found, config) {
+            return raster;
+        }
+        return config.settings.default_image_path.clone();
     }
+    convert::convert_for_display(&found, config)
 }
 
 fn find_system_marquee(
@@ -38,52 +58,72 @@ fn find_system_marquee(
     config: &Config,
     systems: &HashMap<String, String>,
 ) -> Option<PathBuf> {
-    let system_folder = systems.get(system_name).map(|s| s.as_str()).unwrap_or(system_name);
-    let marquee_path_str = config.settings.system_file_path.replace("{system_name}", system_folder);
+    let system_folder = systems::folder(systems, system_name);
+    let marquee_path_str = render_file_name(config.templates.system_file.render(&[("system_name", Value::from(system_folder))]))?;
     let full_marquee_path = config.settings.system_marquee_path.join(marquee_path_str);
-    find_file(&full_marquee_path, &config.settings.accepted_formats)
+    config.formats.find(&full_marquee_path)
 }
 
 fn find_game_marquee(
     system_name: &str,
     game_name: &str,
+    display_name: Option<&str>,
+    layout: &Layout,
     config: &Config,
     systems: &HashMap<String, String>,
 ) -> Option<PathBuf> {
-    let system_folder = systems.get(system_name).map(|s| s.as_str()).unwrap_or(system_name);
-    let marquee_path_str = config.settings.marquee_file_path
-        .replace("{system_name}", system_folder)
-        .replace("{game_name}", game_name);
+    if let Some(path) = find_real_game_marquee(system_name, game_name, config, systems) {
+        return Some(path);
+    }
 
-    let full_marquee_path = config.settings.marquee_image_path.join(marquee_path_str);
-    if let Some(path) = find_file(&full_marquee_path, &config.settings.accepted_formats) {
+    // A marquee generated earlier (F7, a layout key or generate-all) beats the system logo
+    let system_folder = systems::folder(systems, system_name);
+    if let Some(path) = generator::cached_marquee(system_name, system_folder, game_name, layout, config) {
         return Some(path);
     }
 
-    let marquee_path_default_str = config.settings.marquee_file_path_default
-        .replace("{system_name}", system_folder)
-        .replace("{game_name}", game_name);
+    let system_marquee = find_system_marquee(system_name, config, systems);
+    if let Some(path) =
+        fallback::text_fallback_marquee(system_name, system_folder, game_name, display_name, system_marquee.as_deref(), config)
+    {
+        return Some(path);
+    }
+    system_marquee
+}
 
-    let full_marquee_path_default = config.settings.marquee_image_path_default.join(marquee_path_default_str);
-    if let Some(path) = find_file(&full_marquee_path_default, &config.settings.accepted_formats) {
+/// A game marquee provided by the user or a scraper, ignoring generated ones.
+pub fn find_real_game_marquee(
+    system_name: &str,
+    game_name: &str,
+    config: &Config,
+    systems: &HashMap<String, String>,
+) -> Option<PathBuf> {
+    let system_folder = systems::folder(systems, system_name);
+    let marquee_path_str = render_file_name(config.templates.marquee_file.render(&[
+        ("system_name", Value::from(system_folder)),
+        ("game_name", Value::from(game_name)),
+    ]))?;
+
+    let full_marquee_path = config.settings.marquee_image_path.join(marquee_path_str);
+    if let Some(path) = config.formats.find(&full_marquee_path) {
         return Some(path);
     }
 
-    find_system_marquee(system_name, config, systems)
+    let marquee_path_default_str = render_file_name(config.templates.marquee_file_default.render(&[
+        ("system_name", Value::from(system_folder)),
+        ("game_name", Value::from(game_name)),
+    ]))?;
+
+    let full_marquee_path_default = config.settings.marquee_image_path_default.join(marquee_path_default_str);
+    config.formats.find(&full_marquee_path_default)
 }
 
 fn find_collection_marquee(collection_name: &str, config: &Config) -> Option<PathBuf> {
-    let marquee_path_str = config.settings.collection_file_path.replace("{collection_name}", collection_name);
+    let marquee_path_str = render_file_name(config.templates.collection_file.render(&[("collection_name", Value::from(collection_name))]))?;
     let full_marquee_path = config.settings.collection_marquee_path.join(marquee_path_str);
-    find_file(&full_marquee_path, &config.settings.accepted_formats)
+    config.formats.find(&full_marquee_path)
 }
 
-fn find_file(base_path: &Path, accepted_formats: &str) -> Option<PathBuf> {
-    for ext in accepted_formats.split(',') {
-        let path = base_path.with_extension(ext.trim());
-        if path.exists() {
-            return Some(path);
-        }
-    }
-    None
+fn render_file_name(rendered: Result<String, TemplateError>) -> Option<String> {
+    rendered.map_err(|e| error!("Failed to build marquee file name: {}", e)).ok()
 }
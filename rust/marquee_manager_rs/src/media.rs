@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+/// What a media file holds, which decides how it is prepared and played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaKind {
+    Still,
+    Animated,
+    Video,
+    Vector,
+}
+
+/// What handling a media kind needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Auto-convert can fit it to the marquee geometry.
+    pub convertible: bool,
+    /// It must be rendered to a bitmap before it is displayed.
+    pub rasterized: bool,
+    /// It plays over time, so Loop, Mute and Start apply.
+    pub timed: bool,
+    /// The image decoder reads it, for previews and headless outputs.
+    pub decodable: bool,
+}
+
+const EXTENSIONS: &[(&str, MediaKind)] = &[
+    ("png", MediaKind::Still),
+    ("jpg", MediaKind::Still),
+    ("jpeg", MediaKind::Still),
+    ("bmp", MediaKind::Still),
+    ("webp", MediaKind::Still),
+    ("gif", MediaKind::Animated),
+    ("mp4", MediaKind::Video),
+    ("m4v", MediaKind::Video),
+    ("mkv", MediaKind::Video),
+    ("webm", MediaKind::Video),
+    ("avi", MediaKind::Video),
+    ("mov", MediaKind::Video),
+    ("svg", MediaKind::Vector),
+];
+
+impl MediaKind {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        EXTENSIONS
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(extension))
+            .map(|(_, kind)| *kind)
+    }
+
+    /// The kind of `path`, from its extension.
+    pub fn of(path: &Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    /// The name `[Format:<kind>]` sections use.
+    pub fn name(self) -> &'static str {
+        match self {
+            MediaKind::Still => "still",
+            MediaKind::Animated => "animated",
+            MediaKind::Video => "video",
+            MediaKind::Vector => "vector",
+        }
+    }
+
+    pub fn capabilities(self) -> Capabilities {
+        match self {
+            MediaKind::Still => Capabilities { convertible: true, rasterized: false, timed: false, decodable: true },
+            MediaKind::Animated => Capabilities { convertible: false, rasterized: false, timed: true, decodable: true },
+            MediaKind::Video => Capabilities { convertible: false, rasterized: false, timed: true, decodable: false },
+            MediaKind::Vector => Capabilities { convertible: false, rasterized: true, timed: false, decodable: false },
+        }
+    }
+}
+
+/// One entry of `AcceptedFormats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Format {
+    pub extension: String,
+    /// None for extensions this application does not know, which are handed to the player as is.
+    pub kind: Option<MediaKind>,
+}
+
+/// The `AcceptedFormats` setting: the extensions marquees are looked for with, in priority
+/// order.
+#[derive(Debug, Clone, Default)]
+pub struct AcceptedFormats {
+    pub formats: Vec<Format>,
+}
+
+impl AcceptedFormats {
+    /// Parses the comma separated list, skipping empty entries.
+    pub fn parse(text: &str) -> Self {
+        let formats = text
+            .split(',')
+            .map(|extension| extension.trim().trim_start_matches('.').to_string())
+            .filter(|extension| !extension.is_empty())
+            .map(|extension| Format { kind: MediaKind::from_extension(&extension), extension })
+            .collect();
+        AcceptedFormats { formats }
+    }
+
+    /// The first existing file named `base_path` with an accepted extension.
+    pub fn find(&self, base_path: &Path) -> Option<PathBuf> {
+        self.formats
+            .iter()
+            .map(|format| base_path.with_extension(&format.extension))
+            .find(|path| path.exists())
+    }
+}
@@ -0,0 +1,32 @@
+use marquee_core::config;
+use marquee_core::es_settings::{self, EsSettings};
+use marquee_core::i18n::Catalog;
+use marquee_core::ini::Document;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The messages of `language`, the `Language` setting.
+pub fn catalog(language: &str) -> Catalog {
+    Catalog::new(language, marquee_core::builtin_catalogs!())
+}
+
+/// The messages of the `Language` set in the configuration file at `path`, else of the
+/// frontend settings like [`Config::load_config`](crate::config::Config::load_config), for
+/// commands that run without loading the whole configuration.
+pub fn catalog_for_file(path: &Path) -> Catalog {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let document = Document::parse(&content);
+    if let Some(language) = document.get("Settings", "Language").filter(|language| !language.is_empty()) {
+        return catalog(language);
+    }
+    let Ok(plugin_path) = config::plugin_path(path) else {
+        return catalog("");
+    };
+    let retrobat_path = config::find_retrobat_path(document.get("Settings", "RetroBatPath").map(PathBuf::from))
+        .map(|retrobat_path| plugin_path.join(retrobat_path));
+    let setting = document.get("Settings", "ESSettingsPath").map(Path::new);
+    match es_settings::resolve_path(setting, retrobat_path.as_deref(), &plugin_path) {
+        Ok(es_settings_path) => catalog(EsSettings::load_or_default(&es_settings_path).language().unwrap_or_default()),
+        Err(_) => catalog(""),
+    }
+}
@@ -0,0 +1,243 @@
+use crate::config::default_setting;
+use crate::template::Action;
+use crate::validate::{self, Report};
+use marquee_core::i18n::Catalog;
+use marquee_core::ini::Document;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The configuration version written by this release, in `[Settings] ConfigVersion`.
+pub const CURRENT_VERSION: u32 = 2;
+pub const VERSION_KEY: &str = "ConfigVersion";
+
+// Keys only the Python scripts wrote: a config.ini with one of them predates the versioning
+const PYTHON_KEYS: &[&str] = &["MPVShowText", "MPVPushRetroAchievementsDatas", "IPCChannelDMD", "MarqueeAutoGeneration"];
+// Python settings with no equivalent: the ImageMagick pipeline, replaced by the built-in
+// conversion and generator, the DMD and fanart features, and MPVShowText whose message has no
+// duration (without an mpv-show-text command, messages are sent to mpv directly)
+const OBSOLETE_PYTHON_KEYS: &[&str] = &[
+    "MPVShowText",
+    "MPVPushRetroAchievementsDatas",
+    "IPCChannelDMD",
+    "ActiveDMD",
+    "ActiveSystemsDMD",
+    "ESCloseAllIfStop",
+    "FanartSystemFilePath",
+    "FanartGameFilePath",
+    "IMPath",
+    "IMConvertCommand",
+    "IMConvertCommandSVG",
+    "IMConvertCommandMarqueeGen",
+    "IMConvertCommandMarqueeGenLogo",
+    "IMConvertCommandMarqueeGenGradientLogo",
+    "MarqueeAutoGeneration",
+    "MarqueeCompose",
+    "MarqueeBackgroundColor",
+    "MarqueeWhiteTextAlternativNameSuffix",
+    "MarqueeMameOutput",
+    "MarqueeSupermodelOutput",
+    "MarqueeRetroAchievements",
+    "MarqueePinballDMD",
+];
+// The Python launch command passed the SVG background colour to mpv through this setting
+const BACKGROUND_PLACEHOLDER: &str = "{MarqueeBackgroundCodeColor}";
+
+// Each step brings a configuration to its version from the one before
+type Step = fn(&mut Document, &mut Vec<Change>);
+const STEPS: &[(u32, Step)] = &[(1, from_python), (2, to_version_2)];
+
+// The [Settings] keys version 2 added, those without a default are left out of the file
+const VERSION_2_KEYS: &[&str] = &[
+    "MarqueeAutoConvertFill",
+    "MarqueeConvertCachePath",
+    "MarqueeTextFallback",
+    "MarqueeTextFallbackCachePath",
+    "GeneratedCachePath",
+    "CacheMaxSize",
+    "SvgBackground",
+    "SvgCachePath",
+    "ESSystemsPath",
+    "ESEventFilePath",
+    "ESSettingsPath",
+    "DisplayBackend",
+    "HeadlessOutputPath",
+    "GeneratorTemplatesPath",
+    "GeneratorEncoderCommand",
+    "GeneratorLayoutsPath",
+    "GeneratorLogoAnchor",
+    "GeneratorGradient",
+    "GeneratorFanartOffset",
+    "OverlayEnabled",
+    "OverlayTemplate",
+    "OverlaySeparator",
+    "OverlayPosition",
+    "OverlayFontSize",
+    "OverlayDuration",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Renamed { section: String, from: String, to: String },
+    Replaced { section: String, key: String, from: String, to: String },
+    Obsolete { section: String, key: String },
+    Added { section: String, key: String, value: String },
+}
+
+impl Change {
+    /// Whether the change alters what the application reads, as opposed to spelling out a
+    /// default that already applies.
+    pub fn is_needed(&self) -> bool {
+        !matches!(self, Change::Added { .. })
+    }
+
+    /// The change in the language of `messages`.
+    pub fn describe(&self, messages: &Catalog) -> String {
+        match self {
+            Change::Renamed { section, from, to } => {
+                messages.format("change_renamed", &[("section", section), ("from", from), ("to", to)])
+            }
+            Change::Replaced { section, key, from, to } => messages.format(
+                "change_replaced",
+                &[("section", section), ("key", key), ("from", from), ("to", to)],
+            ),
+            Change::Obsolete { section, key } => {
+                messages.format("change_obsolete", &[("section", section), ("key", key)])
+            }
+            Change::Added { section, key, value } => {
+                messages.format("change_added", &[("section", section), ("key", key), ("value", value)])
+            }
+        }
+    }
+}
+
+/// The version of a configuration: its `ConfigVersion`, or guessed from its keys for files
+/// written before the key existed.
+pub fn detect_version(document: &Document) -> Result<u32, String> {
+    match document.get("Settings", VERSION_KEY) {
+        Some(version) => match version.parse() {
+            Ok(version) if version > CURRENT_VERSION => Err(format!(
+                "{} {} is newer than this release supports ({})",
+                VERSION_KEY, version, CURRENT_VERSION
+            )),
+            Ok(version) => Ok(version),
+            Err(_) => Err(format!("{} must be a number, got {:?}", VERSION_KEY, version)),
+        },
+        None if PYTHON_KEYS.iter().any(|key| document.get("Settings", key).is_some()) => Ok(0),
+        None => Ok(1),
+    }
+}
+
+/// Brings `document` to the current version, returning what changed.
+pub fn migrate(document: &mut Document) -> Result<Vec<Change>, String> {
+    let version = detect_version(document)?;
+    let mut changes = Vec::new();
+    for (target, step) in STEPS {
+        if version < *target {
+            step(document, &mut changes);
+        }
+    }
+    if version < CURRENT_VERSION {
+        document.set("Settings", VERSION_KEY, &CURRENT_VERSION.to_string());
+    }
+    Ok(changes)
+}
+
+pub struct Migration {
+    pub from_version: u32,
+    pub changes: Vec<Change>,
+    /// What check-config finds in the migrated file.
+    pub report: Report,
+    /// The copy of the original file, None when nothing was written.
+    pub backup: Option<PathBuf>,
+}
+
+/// Migrates the configuration file at `path` in place after copying it next to itself. Nothing
+/// is written when the file is already current, `dry_run` is set or the migrated file would
+/// still have errors.
+pub fn migrate_file(path: &Path, dry_run: bool) -> Result<Migration, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut document = Document::parse(&content);
+    let from_version = detect_version(&document)?;
+    let changes = migrate(&mut document)?;
+
+    let mut report = Report::default();
+    let mut backup = None;
+    if from_version < CURRENT_VERSION {
+        let migrated = document.to_string();
+        report = validate::check_content(path, &migrated);
+        if !dry_run && !report.has_errors() {
+            let backup_path = backup_path(path);
+            fs::copy(path, &backup_path)?;
+            fs::write(path, migrated)?;
+            backup = Some(backup_path);
+        }
+    }
+    Ok(Migration { from_version, changes, report, backup })
+}
+
+// config.ini.bak, or the first free config.ini.bak.<n> so earlier backups are never replaced
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    let mut backup = path.with_file_name(&name);
+    let mut n = 1;
+    while backup.exists() {
+        backup = path.with_file_name(format!("{}.{}", name.to_string_lossy(), n));
+        n += 1;
+    }
+    backup
+}
+
+// Python-era settings: the SVG background took the name of its colour code, the ImageMagick,
+// DMD and fanart settings are no longer used, and commands using placeholders that are gone
+// cannot be loaded
+fn from_python(document: &mut Document, changes: &mut Vec<Change>) {
+    let launch_command = document.get("Settings", "MPVLaunchCommand").unwrap_or_default().to_string();
+    if launch_command.contains(BACKGROUND_PLACEHOLDER) {
+        let background = document.get("Settings", "MarqueeBackgroundCodeColor").unwrap_or("#000000").to_string();
+        document.set("Settings", "MPVLaunchCommand", &launch_command.replace(BACKGROUND_PLACEHOLDER, &background));
+        changes.push(Change::Replaced {
+            section: "Settings".to_string(),
+            key: "MPVLaunchCommand".to_string(),
+            from: BACKGROUND_PLACEHOLDER.to_string(),
+            to: background,
+        });
+    }
+    if document.rename("Settings", "MarqueeBackgroundCodeColor", "SvgBackground") {
+        changes.push(Change::Renamed {
+            section: "Settings".to_string(),
+            from: "MarqueeBackgroundCodeColor".to_string(),
+            to: "SvgBackground".to_string(),
+        });
+    }
+    for key in OBSOLETE_PYTHON_KEYS {
+        if document.comment_out("Settings", key, "Obsolete") {
+            changes.push(Change::Obsolete { section: "Settings".to_string(), key: key.to_string() });
+        }
+    }
+    let events: Vec<String> = document.keys("Commands").into_iter().map(str::to_string).collect();
+    for event in events {
+        let source = document.get("Commands", &event).unwrap_or_default();
+        if Action::parse(&event, source).is_err() && document.comment_out("Commands", &event, "Obsolete") {
+            changes.push(Change::Obsolete { section: "Commands".to_string(), key: event });
+        }
+    }
+}
+
+// Writes out the settings this version introduced with their default values, so users see
+// what they can now configure without the whole list ending up in a short config.ini
+fn to_version_2(document: &mut Document, changes: &mut Vec<Change>) {
+    let missing: Vec<(&str, String)> = VERSION_2_KEYS
+        .iter()
+        .filter(|key| document.get("Settings", key).is_none())
+        .filter_map(|key| Some((*key, default_setting(key)?)))
+        .collect();
+    let Some((first, _)) = missing.first().cloned() else {
+        return;
+    };
+    for (key, value) in missing {
+        document.set("Settings", key, &value);
+        changes.push(Change::Added { section: "Settings".to_string(), key: key.to_string(), value });
+    }
+    document.comment_before("Settings", first, "Added by migrate-config with their default values");
+}
@@ -0,0 +1,479 @@
+use crate::config::{Config, Settings};
+use crate::display::{Display, MpvDisplay, TextStyle, Transition};
+use crate::headless::HeadlessDisplay;
+use crate::process;
+use crate::template::{Escape, Template, TemplateError, Value, EVENTS, GAME_FILE_PLACEHOLDERS, LAUNCH_PLACEHOLDERS};
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const OUTPUT_SECTION_PREFIX: &str = "Output:";
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(2);
+// An mpv that keeps exiting is relaunched after twice the delay of the previous attempt, up to
+// this many doublings
+const MAX_BACKOFF_DOUBLINGS: u32 = 5;
+const DEFAULT_HEADLESS_PATH: &str = "headless";
+
+/// Which implementation renders an output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backend {
+    Mpv,
+    /// Writes frames and status files into the given directory.
+    Headless(PathBuf),
+}
+
+impl Backend {
+    fn parse(value: &str, headless_path: Option<&Path>, settings: &Settings) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "mpv" => Some(Backend::Mpv),
+            "headless" => Some(Backend::Headless(
+                headless_path
+                    .map(|path| settings.plugin_path.join(path))
+                    .unwrap_or_else(|| settings.plugin_path.join(DEFAULT_HEADLESS_PATH)),
+            )),
+            _ => None,
+        }
+    }
+
+    fn from_settings(settings: &Settings) -> Result<Self, Box<dyn std::error::Error>> {
+        match &settings.display_backend {
+            Some(value) => Backend::parse(value, settings.headless_output_path.as_deref(), settings)
+                .ok_or_else(|| format!("Invalid DisplayBackend '{}', expected mpv or headless", value).into()),
+            None => Ok(Backend::Mpv),
+        }
+    }
+
+    fn create_display(&self, output: &Output) -> Box<dyn Display> {
+        match self {
+            Backend::Mpv => Box::new(MpvDisplay::new(&output.ipc_channel, output.screen_number)),
+            Backend::Headless(directory) => {
+                Box::new(HeadlessDisplay::new(&output.name, output.width, output.height, directory))
+            }
+        }
+    }
+}
+
+/// What an output shows when a given event is received.
+#[derive(Debug, Clone)]
+pub enum ContentRule {
+    /// The marquee found by the resolver for the current system or game.
+    Marquee,
+    /// The configured `DefaultImagePath`.
+    Default,
+    /// Leave whatever is currently displayed.
+    Keep,
+    /// Remove the current content.
+    Clear,
+    /// A file name template (`{system_name}`, `{game_name}`) tried with every accepted format.
+    File(Template),
+}
+
+impl ContentRule {
+    fn parse(event: &str, value: &str) -> Result<Self, TemplateError> {
+        match value.trim().to_lowercase().as_str() {
+            "marquee" => Ok(ContentRule::Marquee),
+            "default" => Ok(ContentRule::Default),
+            "keep" => Ok(ContentRule::Keep),
+            "clear" => Ok(ContentRule::Clear),
+            _ => Ok(ContentRule::File(Template::parse(
+                event,
+                value.trim(),
+                GAME_FILE_PLACEHOLDERS,
+                Escape::Raw,
+            )?)),
+        }
+    }
+}
+
+/// A display managed by its own mpv instance, configured by an `[Output:<name>]` section.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub name: String,
+    pub screen_number: i32,
+    pub ipc_channel: String,
+    pub width: i32,
+    pub height: i32,
+    pub launch_command: Template,
+    pub backend: Backend,
+    pub transition: Transition,
+    pub rules: HashMap<String, ContentRule>,
+    /// Whether the output moves to the `ScreenNumber` of the current system's settings.
+    pub follow_system_screen: bool,
+}
+
+impl Output {
+    /// The single output described by `[Settings]`, used when no `[Output:...]` section exists.
+    fn from_settings(settings: &Settings, launch_command: &Template) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut rules = HashMap::new();
+        rules.insert("system-selected".to_string(), ContentRule::Marquee);
+        rules.insert("game-selected".to_string(), ContentRule::Marquee);
+        Ok(Output {
+            name: "main".to_string(),
+            screen_number: settings.screen_number,
+            ipc_channel: settings.ipc_channel.clone(),
+            width: settings.marquee_width,
+            height: settings.marquee_height,
+            launch_command: launch_command.clone(),
+            backend: Backend::from_settings(settings)?,
+            transition: Transition::Cut,
+            rules,
+            follow_system_screen: true,
+        })
+    }
+
+    fn from_section(
+        name: &str,
+        section: &HashMap<String, String>,
+        settings: &Settings,
+        launch_command: &Template,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut output = Output {
+            name: name.to_string(),
+            screen_number: settings.screen_number,
+            ipc_channel: settings.ipc_channel.clone(),
+            width: settings.marquee_width,
+            height: settings.marquee_height,
+            launch_command: launch_command.clone(),
+            backend: Backend::from_settings(settings)?,
+            transition: Transition::Cut,
+            rules: HashMap::new(),
+            follow_system_screen: !section.contains_key("ScreenNumber"),
+        };
+        let headless_path = section
+            .get("HeadlessOutputPath")
+            .map(|p| PathBuf::from(p.trim()))
+            .or_else(|| settings.headless_output_path.clone());
+
+        for (key, value) in section {
+            match key.as_str() {
+                "ScreenNumber" => output.screen_number = parse_number(name, key, value)?,
+                "IPCChannel" => output.ipc_channel = value.trim().to_string(),
+                "Width" => output.width = parse_number(name, key, value)?,
+                "Height" => output.height = parse_number(name, key, value)?,
+                "Display" => {
+                    output.backend = Backend::parse(value, headless_path.as_deref(), settings).ok_or_else(|| {
+                        format!("Invalid Display '{}' in [{}{}], expected mpv or headless", value.trim(), OUTPUT_SECTION_PREFIX, name)
+                    })?
+                }
+                "HeadlessOutputPath" => {}
+                "Transition" => {
+                    output.transition = Transition::parse(value).ok_or_else(|| {
+                        format!("Invalid Transition '{}' in [{}{}], expected cut or fade[:ms]", value.trim(), OUTPUT_SECTION_PREFIX, name)
+                    })?
+                }
+                "MPVLaunchCommand" => {
+                    output.launch_command =
+                        Template::parse(&format!("[{}{}] MPVLaunchCommand", OUTPUT_SECTION_PREFIX, name), value, LAUNCH_PLACEHOLDERS, Escape::Args)?
+                }
+                // Every other key maps an event name to the content shown for it
+                event if EVENTS.contains(&event) => {
+                    output.rules.insert(event.to_string(), ContentRule::parse(event, value)?);
+                }
+                key => {
+                    return Err(format!("Unknown key {} in [{}{}], expected a setting or an event name", key, OUTPUT_SECTION_PREFIX, name).into());
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn launch_command_line(&self, config: &Config) -> Result<String, TemplateError> {
+        self.launch_command.render(&[
+            ("MPVPath", Value::from(&config.settings.mpv_path)),
+            ("IPCChannel", Value::from(&self.ipc_channel)),
+            ("ScreenNumber", Value::from(self.screen_number)),
+            ("DefaultImagePath", Value::from(&config.settings.default_image_path)),
+            ("MarqueeWidth", Value::from(self.width)),
+            ("MarqueeHeight", Value::from(self.height)),
+        ])
+    }
+
+    fn launch(&self, config: &Config) -> Launch {
+        let launch_command = match self.launch_command_line(config) {
+            Ok(command) => command,
+            Err(e) => {
+                error!("Failed to build MPV launch command for output {}, it stays off until the configuration changes: {}", self.name, e);
+                return Launch::Disabled;
+            }
+        };
+        info!("Launching MPV for output {} with command: {}", self.name, launch_command);
+        match process::spawn_program(&launch_command) {
+            Ok(child) => Launch::Running(child),
+            Err(e) => {
+                error!("Failed to launch MPV for output {}: {}", self.name, e);
+                Launch::Failed
+            }
+        }
+    }
+
+    fn content_for(
+        &self,
+        event: &str,
+        marquee: &mut dyn FnMut() -> Option<PathBuf>,
+        system_name: &str,
+        game_name: &str,
+        config: &Config,
+    ) -> Option<PathBuf> {
+        match self.rules.get(event)? {
+            ContentRule::Keep | ContentRule::Clear => None,
+            ContentRule::Default => Some(config.settings.default_image_path.clone()),
+            ContentRule::Marquee => marquee(),
+            ContentRule::File(template) => {
+                let base = match template.render(&[
+                    ("system_name", Value::from(system_name)),
+                    ("game_name", Value::from(game_name)),
+                ]) {
+                    Ok(base) => base,
+                    Err(e) => {
+                        error!("Failed to build file name for output {}: {}", self.name, e);
+                        return None;
+                    }
+                };
+                Some(
+                    config.formats.find(Path::new(&base))
+                        .unwrap_or_else(|| config.settings.default_image_path.clone()),
+                )
+            }
+        }
+    }
+}
+
+fn parse_number(output: &str, key: &str, value: &str) -> Result<i32, Box<dyn std::error::Error>> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid number '{}' for {} in [{}{}]", value.trim(), key, OUTPUT_SECTION_PREFIX, output).into())
+}
+
+/// Builds the outputs from the `[Output:<name>]` sections, falling back to the single
+/// display described by `[Settings]`.
+pub fn parse_outputs(
+    sections: &HashMap<String, HashMap<String, String>>,
+    settings: &Settings,
+    launch_command: &Template,
+) -> Result<Vec<Output>, Box<dyn std::error::Error>> {
+    let mut outputs = Vec::new();
+    for (section_name, section) in sections {
+        if let Some(name) = section_name.strip_prefix(OUTPUT_SECTION_PREFIX) {
+            outputs.push(Output::from_section(name.trim(), section, settings, launch_command)?);
+        }
+    }
+
+    if outputs.is_empty() {
+        outputs.push(Output::from_settings(settings, launch_command)?);
+    }
+    outputs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(outputs)
+}
+
+// Everything about the mpv instances of a configuration that requires relaunching them
+fn launch_signature(config: &Config) -> Vec<(String, Option<String>)> {
+    mpv_outputs(&config.outputs)
+        .map(|output| (output.name.clone(), output.launch_command_line(config).ok()))
+        .collect()
+}
+
+fn mpv_outputs(outputs: &[Output]) -> impl Iterator<Item = &Output> {
+    outputs.iter().filter(|output| output.backend == Backend::Mpv)
+}
+
+fn with_displays(outputs: Vec<Output>) -> Vec<(Output, Box<dyn Display>)> {
+    outputs
+        .into_iter()
+        .map(|output| {
+            let display = output.backend.create_display(&output);
+            (output, display)
+        })
+        .collect()
+}
+
+// What launching the mpv of an output gave
+enum Launch {
+    Running(Child),
+    Failed,
+    /// The launch command cannot be built, so retrying is pointless.
+    Disabled,
+}
+
+// The mpv of an output, as seen by the supervisor
+#[derive(Default)]
+struct Instance {
+    child: Option<Child>,
+    // Launches in a row that failed or exited, each one doubles the wait before the next
+    failures: u32,
+    retry_at: Option<Instant>,
+    disabled: bool,
+}
+
+impl Instance {
+    fn start(&mut self, output: &Output, config: &Config) {
+        match output.launch(config) {
+            Launch::Running(child) => self.child = Some(child),
+            Launch::Failed => self.failed(),
+            Launch::Disabled => self.disabled = true,
+        }
+    }
+
+    fn failed(&mut self) {
+        self.failures += 1;
+        let delay = SUPERVISE_INTERVAL * 2u32.pow((self.failures - 1).min(MAX_BACKOFF_DOUBLINGS));
+        self.retry_at = Some(Instant::now() + delay);
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            process::stop(&mut child);
+        }
+    }
+}
+
+/// Launches one mpv instance per mpv output, relaunches the ones that exit and routes
+/// events to the display of each output.
+pub struct OutputManager {
+    outputs: RwLock<Vec<(Output, Box<dyn Display>)>>,
+    // Always locked before `outputs` when both are needed
+    instances: Mutex<HashMap<String, Instance>>,
+    stopping: AtomicBool,
+}
+
+impl OutputManager {
+    pub fn new(outputs: Vec<Output>) -> Self {
+        OutputManager {
+            outputs: RwLock::new(with_displays(outputs)),
+            instances: Mutex::new(HashMap::new()),
+            stopping: AtomicBool::new(false),
+        }
+    }
+
+    fn has_mpv_outputs(&self) -> bool {
+        self.outputs.read().unwrap().iter().any(|(output, _)| output.backend == Backend::Mpv)
+    }
+
+    pub fn launch_all(&self, config: &Config) {
+        if !self.has_mpv_outputs() {
+            return;
+        }
+        process::run_kill_command(config);
+        let mut instances = self.instances.lock().unwrap();
+        self.launch_into(&mut instances, config);
+    }
+
+    fn launch_into(&self, instances: &mut HashMap<String, Instance>, config: &Config) {
+        let outputs = self.outputs.read().unwrap();
+        for (output, _) in outputs.iter().filter(|(output, _)| output.backend == Backend::Mpv) {
+            instances.entry(output.name.clone()).or_default().start(output, config);
+        }
+    }
+
+    /// Blocks, relaunching any mpv instance that has exited until `shutdown` is called.
+    /// `config` is read again on every check, so reloads are picked up.
+    pub fn supervise(&self, config: &RwLock<Arc<Config>>) {
+        while !self.stopping.load(Ordering::SeqCst) {
+            thread::sleep(SUPERVISE_INTERVAL);
+            let mut instances = self.instances.lock().unwrap();
+            if self.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+            let config = config.read().unwrap().clone();
+            let outputs = self.outputs.read().unwrap();
+            for (output, _) in outputs.iter().filter(|(output, _)| output.backend == Backend::Mpv) {
+                let instance = instances.entry(output.name.clone()).or_default();
+                if instance.disabled {
+                    continue;
+                }
+                if let Some(child) = &mut instance.child {
+                    if matches!(child.try_wait(), Ok(None)) {
+                        instance.failures = 0;
+                        continue;
+                    }
+                    instance.child = None;
+                    instance.failed();
+                }
+                if instance.retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+                    continue;
+                }
+                warn!("MPV for output {} is not running, relaunching...", output.name);
+                instance.start(output, &config);
+            }
+        }
+    }
+
+    /// Switches to the outputs of a reloaded configuration. mpv is only relaunched when its
+    /// launch command changed; returns whether it was.
+    pub fn reconfigure(&self, old: &Config, new: &Config) -> bool {
+        let relaunch = launch_signature(old) != launch_signature(new);
+        let mut instances = self.instances.lock().unwrap();
+        if self.stopping.load(Ordering::SeqCst) {
+            return false;
+        }
+        if relaunch {
+            instances.values_mut().for_each(Instance::stop);
+            instances.clear();
+        }
+        *self.outputs.write().unwrap() = with_displays(new.outputs.clone());
+        if relaunch {
+            self.launch_into(&mut instances, new);
+        }
+        relaunch
+    }
+
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let mut instances = self.instances.lock().unwrap();
+        instances.values_mut().for_each(Instance::stop);
+    }
+
+    /// Updates every output that has a rule for `event`. The marquee is only resolved if at
+    /// least one output asks for it.
+    pub fn handle_event(
+        &self,
+        event: &str,
+        system_name: &str,
+        game_name: &str,
+        resolve_marquee: impl Fn() -> PathBuf,
+        config: &Config,
+    ) {
+        let mut resolved: Option<PathBuf> = None;
+        let mut marquee = || Some(resolved.get_or_insert_with(&resolve_marquee).clone());
+
+        for (output, display) in self.outputs.read().unwrap().iter() {
+            if matches!(output.rules.get(event), Some(ContentRule::Clear)) {
+                info!("Clearing output {}", output.name);
+                display.clear(config);
+            } else if let Some(file) = output.content_for(event, &mut marquee, system_name, game_name, config) {
+                info!("Updating output {} to: {:?}", output.name, file);
+                if output.follow_system_screen {
+                    display.move_to_screen(config.settings.screen_number);
+                }
+                display.transition(&file, output.transition, config);
+            }
+        }
+    }
+
+    /// Shows `file` on every output that displays marquees.
+    pub fn show_marquee(&self, file: &Path, config: &Config) {
+        for (output, display) in self.outputs.read().unwrap().iter() {
+            if output.rules.values().any(|rule| matches!(rule, ContentRule::Marquee)) {
+                if output.follow_system_screen {
+                    display.move_to_screen(config.settings.screen_number);
+                }
+                display.transition(file, output.transition, config);
+            }
+        }
+    }
+
+    /// Shows a text message on every output that displays marquees.
+    pub fn show_text(&self, text: &str, style: &TextStyle, config: &Config) {
+        for (output, display) in self.outputs.read().unwrap().iter() {
+            if output.rules.values().any(|rule| matches!(rule, ContentRule::Marquee)) {
+                display.show_text(text, style, config);
+            }
+        }
+    }
+}
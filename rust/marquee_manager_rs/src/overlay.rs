@@ -0,0 +1,95 @@
+use crate::config::Settings;
+use crate::display::TextStyle;
+use crate::gamelist::GameMetadata;
+use crate::template::{Escape, Template, Value, OVERLAY_PLACEHOLDERS};
+use std::time::Duration;
+
+/// The game information line shown over the marquee when a game is selected.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    // The template split on the separator; segments whose placeholders are all empty are dropped
+    segments: Vec<Template>,
+    separator: String,
+    pub style: TextStyle,
+}
+
+impl Overlay {
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        if !settings.overlay_enabled {
+            return Ok(None);
+        }
+
+        // INI values are trimmed, so a separator with spaces around it can be written in quotes
+        let separator = settings
+            .overlay_separator
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or(&settings.overlay_separator)
+            .to_string();
+        let mut segments = Vec::new();
+        let sources: Vec<&str> = if separator.is_empty() {
+            vec![settings.overlay_template.as_str()]
+        } else {
+            settings.overlay_template.split(separator.as_str()).collect()
+        };
+        for source in sources {
+            segments.push(Template::parse("OverlayTemplate", source, OVERLAY_PLACEHOLDERS, Escape::Raw)?);
+        }
+
+        let (align_x, align_y) = TextStyle::parse_position(&settings.overlay_position).ok_or_else(|| {
+            format!(
+                "Invalid OverlayPosition '{}', expected e.g. top-left, bottom-center or center",
+                settings.overlay_position
+            )
+        })?;
+
+        Ok(Some(Overlay {
+            segments,
+            separator,
+            style: TextStyle {
+                duration: Duration::from_secs(settings.overlay_duration.max(0) as u64),
+                font_size: settings.overlay_font_size,
+                align_x,
+                align_y,
+            },
+        }))
+    }
+
+    pub fn render(&self, metadata: &GameMetadata, system_name: &str, game_name: &str) -> Option<String> {
+        let values: Vec<(&str, &str)> = vec![
+            ("name", metadata.name.as_deref().unwrap_or(game_name)),
+            ("year", metadata.year().unwrap_or("")),
+            ("developer", metadata.developer.as_deref().unwrap_or("")),
+            ("publisher", metadata.publisher.as_deref().unwrap_or("")),
+            ("genre", metadata.genre.as_deref().unwrap_or("")),
+            ("players", metadata.players.as_deref().unwrap_or("")),
+            ("rating", metadata.rating.as_deref().unwrap_or("")),
+            ("system_name", system_name),
+            ("game_name", game_name),
+        ];
+
+        let mut rendered = Vec::new();
+        for segment in &self.segments {
+            let mut placeholders = segment.placeholders().peekable();
+            let has_placeholders = placeholders.peek().is_some();
+            let all_empty = placeholders.all(|name| {
+                values.iter().find(|(n, _)| *n == name).map(|(_, v)| v.trim().is_empty()).unwrap_or(true)
+            });
+            if has_placeholders && all_empty {
+                continue;
+            }
+            let segment_values: Vec<(&str, Value)> = values.iter().map(|(n, v)| (*n, Value::from(*v))).collect();
+            match segment.render(&segment_values) {
+                Ok(text) if !text.trim().is_empty() => rendered.push(text),
+                Ok(_) => {}
+                Err(e) => log::error!("Failed to render overlay: {}", e),
+            }
+        }
+
+        if rendered.is_empty() {
+            None
+        } else {
+            Some(rendered.join(&self.separator))
+        }
+    }
+}
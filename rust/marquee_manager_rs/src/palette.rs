@@ -0,0 +1,118 @@
+use image::{DynamicImage, GenericImageView, Rgba};
+use std::collections::HashMap;
+
+// Colours are counted on a thumbnail, in buckets of 4 bits per channel
+const THUMBNAIL_SIZE: u32 = 64;
+// Accent candidates must cover this share of the image...
+const ACCENT_MIN_SHARE: f32 = 0.01;
+// ...and be at least this far from the dominant colour (RGB distance)
+const ACCENT_MIN_DISTANCE: f32 = 80.0;
+
+/// Which colour of a palette a design refers to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteRole {
+    Dominant,
+    Accent,
+}
+
+impl PaletteRole {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "dominant" => Some(PaletteRole::Dominant),
+            "accent" => Some(PaletteRole::Accent),
+            _ => None,
+        }
+    }
+}
+
+/// The main colours of an image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    /// The most common colour.
+    pub dominant: Rgba<u8>,
+    /// A common, saturated colour that stands out from the dominant one.
+    pub accent: Rgba<u8>,
+}
+
+impl Palette {
+    pub fn extract(image: &DynamicImage) -> Self {
+        let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+        let mut buckets: HashMap<(u8, u8, u8), (u32, [u32; 3])> = HashMap::new();
+        let mut total = 0;
+        for (_, _, pixel) in thumbnail.pixels() {
+            let [r, g, b, a] = pixel.0;
+            if a < 128 {
+                continue;
+            }
+            let bucket = buckets.entry((r >> 4, g >> 4, b >> 4)).or_insert((0, [0; 3]));
+            bucket.0 += 1;
+            bucket.1[0] += r as u32;
+            bucket.1[1] += g as u32;
+            bucket.1[2] += b as u32;
+            total += 1;
+        }
+        let colours: Vec<(u32, Rgba<u8>)> = buckets
+            .values()
+            .map(|(count, sums)| {
+                let average = |sum: u32| (sum / count) as u8;
+                (*count, Rgba([average(sums[0]), average(sums[1]), average(sums[2]), 255]))
+            })
+            .collect();
+
+        let dominant = colours
+            .iter()
+            .max_by_key(|(count, _)| *count)
+            .map(|(_, colour)| *colour)
+            .unwrap_or(Rgba([0, 0, 0, 255]));
+        let min_count = (total as f32 * ACCENT_MIN_SHARE).max(1.0) as u32;
+        let accent = colours
+            .iter()
+            .filter(|(count, colour)| *count >= min_count && distance(*colour, dominant) >= ACCENT_MIN_DISTANCE)
+            .map(|(count, colour)| (saturation(*colour) * (*count as f32).sqrt(), *colour))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, colour)| colour)
+            .unwrap_or_else(|| contrast(dominant));
+        Palette { dominant, accent }
+    }
+
+    pub fn get(&self, role: PaletteRole) -> Rgba<u8> {
+        match role {
+            PaletteRole::Dominant => self.dominant,
+            PaletteRole::Accent => self.accent,
+        }
+    }
+}
+
+fn distance(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .take(3)
+        .map(|(a, b)| (*a as f32 - *b as f32).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+fn saturation(colour: Rgba<u8>) -> f32 {
+    let [r, g, b, _] = colour.0;
+    let max = r.max(g).max(b) as f32;
+    let min = r.min(g).min(b) as f32;
+    if max == 0.0 {
+        0.0
+    } else {
+        (max - min) / max
+    }
+}
+
+// For single-colour images: a lighter shade of dark colours and a darker shade of light ones
+fn contrast(colour: Rgba<u8>) -> Rgba<u8> {
+    let [r, g, b, a] = colour.0;
+    let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    let shift = |channel: u8| {
+        if luma < 128.0 {
+            channel as f32 + (255.0 - channel as f32) * 0.5
+        } else {
+            channel as f32 * 0.5
+        }
+    };
+    Rgba([shift(r) as u8, shift(g) as u8, shift(b) as u8, a])
+}
@@ -1,61 +1,129 @@
 use crate::config::Config;
+use crate::template::{Action, Value};
 use log::{error, info};
+use marquee_core::ipc;
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
 
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
-pub fn launch_media_player(config: &Config) {
-    kill_media_player(config);
-
-    let launch_command = config.settings.mpv_launch_command
-        .replace("{MPVPath}", config.settings.mpv_path.to_str().unwrap_or(""))
-        .replace("{IPCChannel}", &config.settings.ipc_channel)
-        .replace("{ScreenNumber}", &config.settings.screen_number.to_string())
-        .replace("{DefaultImagePath}", config.settings.default_image_path.to_str().unwrap_or(""));
-
-    info!("Launching MPV with command: {}", launch_command);
-
+// The command line is passed verbatim so that the escaping done by the templates is what
+// cmd.exe actually sees, instead of being re-quoted by the standard library.
+pub fn shell_command(command_line: &str) -> Command {
     let mut cmd = Command::new("cmd");
-    cmd.arg("/C").arg(launch_command)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null());
+    cmd.arg("/C");
+    #[cfg(windows)]
+    cmd.raw_arg(command_line);
+    #[cfg(not(windows))]
+    cmd.arg(command_line);
+    cmd.stdout(Stdio::null()).stderr(Stdio::null());
+    cmd
+}
 
+/// Spawns the program named by the first word of `command_line`, quoted or not, without a
+/// console window. It is not run through cmd, so the child is the program itself and killing
+/// it leaves any other instance alone.
+pub fn spawn_program(command_line: &str) -> std::io::Result<Child> {
+    let (program, arguments) = split_program(command_line);
+    let mut cmd = Command::new(program);
     #[cfg(windows)]
     {
         const CREATE_NO_WINDOW: u32 = 0x08000000;
-        cmd.creation_flags(CREATE_NO_WINDOW);
+        cmd.raw_arg(arguments).creation_flags(CREATE_NO_WINDOW);
+    }
+    #[cfg(not(windows))]
+    cmd.args(split_arguments(arguments));
+    cmd.stdout(Stdio::null()).stderr(Stdio::null()).spawn()
+}
+
+fn split_program(command_line: &str) -> (&str, &str) {
+    let line = command_line.trim_start();
+    let (program, arguments) = match line.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+        None => line.split_once(char::is_whitespace).unwrap_or((line, "")),
+    };
+    (program, arguments.trim_start())
+}
+
+// Windows hands the command line to the program as is, elsewhere it has to be split here
+#[cfg(not(windows))]
+fn split_arguments(arguments: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+    for c in arguments.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => split.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
     }
+    split.extend(current);
+    split
+}
 
-    match cmd.spawn() {
-        Ok(_) => info!("MPV launched successfully."),
-        Err(e) => error!("Failed to launch MPV: {}", e),
+/// Stops a program started by `spawn_program` and waits for it to exit.
+pub fn stop(child: &mut Child) {
+    if let Err(e) = child.kill() {
+        error!("Failed to stop process {}: {}", child.id(), e);
     }
+    let _ = child.wait();
+}
+
+/// Runs `MPVKillCommand`, if set, to close the media players a previous run left open.
+pub fn run_kill_command(config: &Config) {
+    let Some(template) = &config.templates.mpv_kill else {
+        return;
+    };
+    let kill_command = match template.render(&[("MPVPath", Value::from(&config.settings.mpv_path))]) {
+        Ok(command) => command,
+        Err(e) => {
+            error!("Failed to build MPV kill command: {}", e);
+            return;
+        }
+    };
+    info!("Killing MPV with command: {}", kill_command);
+    let _ = shell_command(&kill_command).status();
 }
 
-pub fn kill_media_player(config: &Config) {
-    info!("Killing MPV with command: {}", &config.settings.mpv_kill_command);
-    let _ = Command::new("cmd")
-        .arg("/C")
-        .arg(&config.settings.mpv_kill_command)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status();
-}
-
-pub fn update_marquee(marquee_path: &Path, config: &Config) {
-    if let Some(command_template) = config.commands.commands.get("game-selected") {
-        let command = command_template
-            .replace("{marquee_file}", marquee_path.to_str().unwrap_or(""))
-            .replace("{IPCChannel}", &config.settings.ipc_channel);
-
-        info!("Updating marquee with command: {}", command);
-        let _ = Command::new("cmd")
-            .arg("/C")
-            .arg(command)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status();
+/// Runs the `[Commands]` entry configured for `event`, if any.
+pub fn run_event_command(event: &str, values: &[(&str, Value)], ipc_channel: &str, config: &Config) {
+    let action = match config.templates.commands.get(event) {
+        Some(action) => action,
+        None => return,
+    };
+
+    match action {
+        Action::Ipc(template) => match template.render(values) {
+            Ok(command) => {
+                info!("Sending IPC command for {}: {}", event, command);
+                ipc::send_command(ipc_channel, &command);
+            }
+            Err(e) => error!("Failed to build IPC command for {}: {}", event, e),
+        },
+        Action::Shell(template) => match template.render(values) {
+            Ok(command) => {
+                info!("Running command for {}: {}", event, command);
+                let _ = shell_command(&command).status();
+            }
+            Err(e) => error!("Failed to build command for {}: {}", event, e),
+        },
     }
 }
+
+pub fn update_marquee(marquee_path: &Path, ipc_channel: &str, config: &Config) {
+    run_event_command(
+        "game-selected",
+        &[
+            ("marquee_file", Value::from(marquee_path)),
+            ("IPCChannel", Value::from(ipc_channel)),
+            ("DefaultImagePath", Value::from(&config.settings.default_image_path)),
+        ],
+        ipc_channel,
+        config,
+    );
+}
@@ -0,0 +1,112 @@
+use crate::media::MediaKind;
+use std::collections::HashMap;
+use std::path::Path;
+
+const FORMAT_SECTION_PREFIX: &str = "Format:";
+
+/// How media is scaled to the output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scale {
+    /// Keep the aspect ratio and letterbox.
+    Fit,
+    /// Keep the aspect ratio and crop to fill the output.
+    Fill,
+    /// Ignore the aspect ratio.
+    Stretch,
+}
+
+/// Playback options applied when a file of a given format is loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackProfile {
+    /// Loop forever, or play once and hold the last frame.
+    pub loop_file: bool,
+    pub mute: bool,
+    /// Start offset in seconds.
+    pub start: f64,
+    /// How long still images stay up, `None` for as long as nothing else is loaded.
+    pub image_duration: Option<f64>,
+    pub scale: Scale,
+}
+
+impl Default for PlaybackProfile {
+    fn default() -> Self {
+        PlaybackProfile {
+            loop_file: true,
+            mute: true,
+            start: 0.0,
+            image_duration: None,
+            scale: Scale::Fit,
+        }
+    }
+}
+
+impl PlaybackProfile {
+    fn from_section(section_name: &str, section: &HashMap<String, String>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut profile = PlaybackProfile::default();
+        for (key, value) in section {
+            let value = value.trim();
+            let invalid = |expected: &str| format!("Invalid {} '{}' in [{}], expected {}", key, value, section_name, expected);
+            match key.as_str() {
+                "Loop" => profile.loop_file = parse_bool(value).ok_or_else(|| invalid("true or false"))?,
+                "Mute" => profile.mute = parse_bool(value).ok_or_else(|| invalid("true or false"))?,
+                "Start" => profile.start = value.parse().map_err(|_| invalid("a number of seconds"))?,
+                "ImageDuration" => {
+                    profile.image_duration = match value.to_lowercase().as_str() {
+                        "inf" => None,
+                        _ => Some(value.parse().map_err(|_| invalid("a number of seconds or inf"))?),
+                    }
+                }
+                "Scale" => {
+                    profile.scale = match value.to_lowercase().as_str() {
+                        "fit" => Scale::Fit,
+                        "fill" => Scale::Fill,
+                        "stretch" => Scale::Stretch,
+                        _ => return Err(invalid("fit, fill or stretch").into()),
+                    }
+                }
+                _ => return Err(format!("Unknown key {} in [{}]", key, section_name).into()),
+            }
+        }
+        Ok(profile)
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" => Some(true),
+        "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Playback profiles from the `[Format:<ext>[,<ext>...]]` sections, keyed by lowercase extension
+/// or media kind name (`still`, `animated`, `video`, `vector`).
+#[derive(Debug, Clone, Default)]
+pub struct Profiles {
+    by_extension: HashMap<String, PlaybackProfile>,
+}
+
+impl Profiles {
+    pub fn parse(sections: &HashMap<String, HashMap<String, String>>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut by_extension = HashMap::new();
+        for (section_name, section) in sections {
+            if let Some(extensions) = section_name.strip_prefix(FORMAT_SECTION_PREFIX) {
+                let profile = PlaybackProfile::from_section(section_name, section)?;
+                for ext in extensions.split(',') {
+                    by_extension.insert(ext.trim().to_lowercase(), profile.clone());
+                }
+            }
+        }
+        Ok(Profiles { by_extension })
+    }
+
+    /// The profile for `path`'s extension, else for its media kind, else the default profile.
+    pub fn for_file(&self, path: &Path) -> PlaybackProfile {
+        let by_extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.by_extension.get(&ext.to_lowercase()));
+        let by_kind = || MediaKind::of(path).and_then(|kind| self.by_extension.get(kind.name()));
+        by_extension.or_else(by_kind).cloned().unwrap_or_default()
+    }
+}
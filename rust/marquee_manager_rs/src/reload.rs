@@ -0,0 +1,124 @@
+use crate::config::Config;
+use crate::outputs::OutputManager;
+use crate::validate::{self, Report};
+use log::{error, info};
+use notify::{Config as NotifyConfig, RecommendedWatcher, RecursiveMode, Watcher};
+use marquee_core::systems;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc::channel, Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+// Editors often write a file in several steps, so changes are applied once they settle
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+/// The running configuration. Readers take a snapshot with [`current`], so a reload never
+/// changes the configuration in the middle of handling an event.
+pub type SharedConfig = Arc<RwLock<Arc<Config>>>;
+/// The system name to rom folder map read from `ESSystemsPath`, rebuilt when the setting changes.
+pub type SharedSystems = Arc<RwLock<Arc<HashMap<String, String>>>>;
+
+pub fn current<T>(shared: &RwLock<Arc<T>>) -> Arc<T> {
+    shared.read().unwrap().clone()
+}
+
+/// Watches the configuration file and swaps in every valid new version.
+pub fn watch_config(
+    path: &Path,
+    config: SharedConfig,
+    systems: SharedSystems,
+    outputs: Arc<OutputManager>,
+) -> notify::Result<()> {
+    let (tx, rx) = channel();
+    let mut watcher: RecommendedWatcher = Watcher::new(tx, NotifyConfig::default())?;
+    // The folder is watched, as editors often replace the file instead of modifying it
+    let folder = match path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        Some(parent) => parent.to_path_buf(),
+        None => PathBuf::from("."),
+    };
+    watcher.watch(&folder, RecursiveMode::NonRecursive)?;
+    info!("Watching {:?} for configuration changes...", path);
+
+    let mut last_content = fs::read_to_string(path).unwrap_or_default();
+    loop {
+        match rx.recv() {
+            Ok(Ok(event)) => {
+                let touches_config = event.paths.iter().any(|changed| changed.file_name() == path.file_name());
+                if !touches_config || !(event.kind.is_modify() || event.kind.is_create()) {
+                    continue;
+                }
+                thread::sleep(SETTLE_DELAY);
+                while rx.try_recv().is_ok() {}
+
+                let content = match fs::read_to_string(path) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        error!("Failed to read {:?}, keeping the previous configuration: {}", path, e);
+                        continue;
+                    }
+                };
+                if content == last_content {
+                    continue;
+                }
+                last_content = content;
+                reload(path, &config, &systems, &outputs);
+            }
+            Ok(Err(e)) => error!("Config watch error: {:?}", e),
+            Err(e) => {
+                error!("Config watch channel closed: {:?}", e);
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn reload(path: &Path, config: &RwLock<Arc<Config>>, systems: &RwLock<Arc<HashMap<String, String>>>, outputs: &OutputManager) {
+    info!("{:?} changed, reloading the configuration...", path);
+    let new = match Config::load_config(path) {
+        Ok(new) => new,
+        Err(e) => {
+            error!("Invalid configuration, keeping the previous one: {}", e);
+            return;
+        }
+    };
+    if new.settings.retrobat_path.is_none() {
+        error!("Invalid configuration, keeping the previous one: RetroBatPath is not set");
+        return;
+    }
+
+    let mut report = Report::default();
+    validate::check_config(&new, &mut report);
+    report.log(&new.messages);
+    if report.has_errors() {
+        error!("Invalid configuration, keeping the previous one");
+        return;
+    }
+
+    // The event watcher follows ESEventFilePath by itself, the systems map is rebuilt here
+    let systems_path = &new.settings.es_systems_path;
+    let new_systems = if *systems_path != current(config).settings.es_systems_path {
+        match systems::load_all_systems_configs(systems_path) {
+            Ok(new_systems) => Some(new_systems),
+            Err(e) => {
+                error!("Failed to load systems from {:?}, keeping the previous configuration: {}", systems_path, e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    let new = Arc::new(new);
+    let old = std::mem::replace(&mut *config.write().unwrap(), new.clone());
+    if let Some(new_systems) = new_systems {
+        *systems.write().unwrap() = Arc::new(new_systems);
+        info!("Systems reloaded from {:?}", new.settings.es_systems_path);
+    }
+    if outputs.reconfigure(&old, &new) {
+        info!("Configuration reloaded, MPV relaunched");
+    } else {
+        info!("Configuration reloaded");
+    }
+}
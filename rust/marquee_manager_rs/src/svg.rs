@@ -0,0 +1,114 @@
+use crate::cache::{self, CacheKey};
+use crate::colour::parse_colour;
+use crate::config::Config;
+use crate::media::MediaKind;
+use image::{DynamicImage, Rgba, RgbaImage};
+use log::{error, info};
+use resvg::usvg::fontdb;
+use resvg::{tiny_skia, usvg};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+// Scanning every installed font takes hundreds of milliseconds on Windows, so it is done once
+static SYSTEM_FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+
+/// The fonts installed on the system, loaded on first use.
+pub fn system_fonts() -> Arc<fontdb::Database> {
+    SYSTEM_FONTS
+        .get_or_init(|| {
+            let mut fonts = fontdb::Database::new();
+            fonts.load_system_fonts();
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+/// Returns a PNG rendering of an SVG file at the marquee resolution, rasterizing it on first
+/// use. Rasters are cached by the SVG file and the render settings.
+pub fn rasterize_for_display(path: &Path, config: &Config) -> Option<PathBuf> {
+    let mut key = CacheKey::new();
+    if let Err(e) = key.file(path) {
+        error!("Failed to read SVG {:?}: {}", path, e);
+        return None;
+    }
+    key.geometry(config).text(&config.settings.svg_background.trim().to_lowercase());
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    let raster_path = key.path(&config.settings.svg_cache_path, &stem, "png");
+    if let Some(cached) = cache::lookup(&raster_path) {
+        return Some(cached);
+    }
+
+    info!("Rasterizing {:?} to {:?}", path, raster_path);
+    let pixmap = match fs::read(path).map_err(|e| e.into()).and_then(|data| rasterize(&data, config)) {
+        Ok(pixmap) => pixmap,
+        Err(e) => {
+            error!("Failed to rasterize SVG {:?}: {}", path, e);
+            return None;
+        }
+    };
+    match cache::store_with(&raster_path, config, |path| Ok(pixmap.save_png(path)?)) {
+        Ok(()) => Some(raster_path),
+        Err(e) => {
+            error!("Failed to write SVG raster {:?}: {}", raster_path, e);
+            None
+        }
+    }
+}
+
+/// Renders SVG data centered inside the marquee area, scaled to fit within the border.
+pub fn rasterize(data: &[u8], config: &Config) -> Result<tiny_skia::Pixmap, Box<dyn std::error::Error>> {
+    let width = config.settings.marquee_width.max(1) as u32;
+    let height = config.settings.marquee_height.max(1) as u32;
+    let border = (config.settings.marquee_border.max(0) as u32).min(width.min(height) / 2);
+    let background = parse_colour(&config.settings.svg_background).unwrap_or(Rgba([0, 0, 0, 255]));
+    render(data, width, height, border, background)
+}
+
+/// Opens an image file for compositing, rendering SVG files to fit `max_width`×`max_height`
+/// on a transparent background.
+pub fn open_image(path: &Path, max_width: u32, max_height: u32) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    if !MediaKind::of(path).is_some_and(|kind| kind.capabilities().rasterized) {
+        return Ok(image::open(path)?);
+    }
+    let data = fs::read(path)?;
+    let pixmap = render(&data, max_width.max(1), max_height.max(1), 0, Rgba([0, 0, 0, 0]))?;
+    let (width, height) = (pixmap.width(), pixmap.height());
+    // tiny-skia stores premultiplied alpha, image expects straight alpha
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let pixel = pixel.demultiply();
+            [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+        })
+        .collect();
+    let image = RgbaImage::from_raw(width, height, pixels).ok_or("Invalid SVG raster")?;
+    Ok(DynamicImage::ImageRgba8(image))
+}
+
+fn render(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    border: u32,
+    background: Rgba<u8>,
+) -> Result<tiny_skia::Pixmap, Box<dyn std::error::Error>> {
+    let inner_width = (width - 2 * border).max(1) as f32;
+    let inner_height = (height - 2 * border).max(1) as f32;
+
+    let options = usvg::Options { fontdb: system_fonts(), ..usvg::Options::default() };
+    let tree = usvg::Tree::from_data(data, &options)?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).ok_or("Invalid marquee size")?;
+    let [red, green, blue, alpha] = background.0;
+    pixmap.fill(tiny_skia::Color::from_rgba8(red, green, blue, alpha));
+
+    let size = tree.size();
+    let scale = (inner_width / size.width()).min(inner_height / size.height());
+    let x = border as f32 + (inner_width - size.width() * scale) / 2.0;
+    let y = border as f32 + (inner_height - size.height() * scale) / 2.0;
+    let transform = tiny_skia::Transform::from_row(scale, 0.0, 0.0, scale, x, y);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
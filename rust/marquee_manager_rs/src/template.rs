@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use crate::config::Settings;
+
+pub use marquee_core::template::{Escape, Template, TemplateError, Value};
+
+// Placeholders each configurable template is allowed to reference
+pub const LAUNCH_PLACEHOLDERS: &[&str] = &[
+    "MPVPath",
+    "IPCChannel",
+    "ScreenNumber",
+    "DefaultImagePath",
+    "MarqueeWidth",
+    "MarqueeHeight",
+];
+pub const KILL_PLACEHOLDERS: &[&str] = &["MPVPath"];
+pub const GAME_FILE_PLACEHOLDERS: &[&str] = &["system_name", "game_name"];
+pub const SYSTEM_FILE_PLACEHOLDERS: &[&str] = &["system_name"];
+pub const COLLECTION_FILE_PLACEHOLDERS: &[&str] = &["collection_name"];
+pub const DESIGN_PLACEHOLDERS: &[&str] = &["roms_path", "images_path", "system_name", "game_name"];
+pub const FALLBACK_DESIGN_PLACEHOLDERS: &[&str] =
+    &["roms_path", "images_path", "system_name", "game_name", "name", "system_logo"];
+pub const ENCODER_PLACEHOLDERS: &[&str] = &["frames", "frame_rate", "output"];
+pub const OVERLAY_PLACEHOLDERS: &[&str] = &[
+    "name",
+    "year",
+    "developer",
+    "publisher",
+    "genre",
+    "players",
+    "rating",
+    "system_name",
+    "game_name",
+];
+// Events sent by EmulationStation that [Commands] and output rules can react to
+pub const EVENTS: &[&str] = &[
+    "quit",
+    "reboot",
+    "shutdown",
+    "config-changed",
+    "controls-changed",
+    "settings-changed",
+    "theme-changed",
+    "game-start",
+    "game-end",
+    "sleep",
+    "wake",
+    "screensaver-start",
+    "screensaver-stop",
+    "screensaver-game-select",
+    "system-select",
+    "system-selected",
+    "game-select",
+    "game-selected",
+    "mpv-show-text",
+];
+// [Commands] entries only get what their event provides: the file being loaded, or the text
+// of an OSD message for mpv-show-text
+pub const COMMAND_PLACEHOLDERS: &[&str] = &["marquee_file", "IPCChannel", "DefaultImagePath"];
+pub const SHOW_TEXT_PLACEHOLDERS: &[&str] = &["message", "duration", "IPCChannel", "DefaultImagePath"];
+
+/// The placeholders the `[Commands]` entry of `event` is rendered with.
+pub fn command_placeholders(event: &str) -> &'static [&'static str] {
+    match event {
+        "mpv-show-text" => SHOW_TEXT_PLACEHOLDERS,
+        _ => COMMAND_PLACEHOLDERS,
+    }
+}
+
+/// A `[Commands]` entry. Entries of the form `echo <command> > {IPCChannel}` are sent straight
+/// to the mpv pipe instead of going through cmd, everything else runs as a shell command.
+#[derive(Debug, Clone)]
+pub enum Action {
+    Ipc(Template),
+    Shell(Template),
+}
+
+impl Action {
+    pub fn parse(key: &str, source: &str) -> Result<Self, TemplateError> {
+        let known = command_placeholders(key);
+        if let Some(payload) = ipc_payload(source) {
+            return Ok(Action::Ipc(Template::parse(key, payload, known, Escape::Ipc)?));
+        }
+        Ok(Action::Shell(Template::parse(key, source, known, Escape::Shell)?))
+    }
+}
+
+fn ipc_payload(source: &str) -> Option<&str> {
+    let body = source.trim().strip_prefix("echo ")?;
+    let redirect = body.rfind('>')?;
+    if body[redirect + 1..].trim() != "{IPCChannel}" {
+        return None;
+    }
+    Some(body[..redirect].trim())
+}
+
+/// Every template of the configuration, parsed and validated once at load time.
+#[derive(Debug, Clone, Default)]
+pub struct Templates {
+    pub mpv_launch: Template,
+    pub mpv_kill: Option<Template>,
+    pub marquee_file: Template,
+    pub marquee_file_default: Template,
+    pub system_file: Template,
+    pub collection_file: Template,
+    pub encoder: Template,
+    pub commands: HashMap<String, Action>,
+}
+
+impl Templates {
+    pub fn parse(settings: &Settings, commands: &HashMap<String, String>) -> Result<Self, TemplateError> {
+        let mut parsed_commands = HashMap::new();
+        for (event, source) in commands {
+            parsed_commands.insert(event.clone(), Action::parse(event, source)?);
+        }
+
+        Ok(Templates {
+            mpv_launch: Template::parse("MPVLaunchCommand", &settings.mpv_launch_command, LAUNCH_PLACEHOLDERS, Escape::Args)?,
+            mpv_kill: settings
+                .mpv_kill_command
+                .as_deref()
+                .filter(|command| !command.trim().is_empty())
+                .map(|command| Template::parse("MPVKillCommand", command, KILL_PLACEHOLDERS, Escape::Shell))
+                .transpose()?,
+            marquee_file: Template::parse("MarqueeFilePath", &settings.marquee_file_path, GAME_FILE_PLACEHOLDERS, Escape::Raw)?,
+            marquee_file_default: Template::parse("MarqueeFilePathDefault", &settings.marquee_file_path_default, GAME_FILE_PLACEHOLDERS, Escape::Raw)?,
+            system_file: Template::parse("SystemFilePath", &settings.system_file_path, SYSTEM_FILE_PLACEHOLDERS, Escape::Raw)?,
+            collection_file: Template::parse("CollectionFilePath", &settings.collection_file_path, COLLECTION_FILE_PLACEHOLDERS, Escape::Raw)?,
+            encoder: Template::parse("GeneratorEncoderCommand", &settings.generator_encoder_command, ENCODER_PLACEHOLDERS, Escape::Shell)?,
+            commands: parsed_commands,
+        })
+    }
+}
@@ -0,0 +1,383 @@
+use crate::config::{self, Config};
+use crate::migrate::{self, Change};
+use crate::outputs::Backend;
+use crate::template::{self, Action, Escape, Template, TemplateError, EVENTS};
+use log::{error, warn};
+use marquee_core::i18n::Catalog;
+use marquee_core::ini::Document;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+// Keys of an [Output:<name>] section besides the event names
+const OUTPUT_KEYS: &[&str] = &[
+    "ScreenNumber",
+    "IPCChannel",
+    "Width",
+    "Height",
+    "Display",
+    "HeadlessOutputPath",
+    "Transition",
+    "MPVLaunchCommand",
+];
+// [Settings] keys read by retroachievements_rs and utility_rs, which share this file
+const SHARED_SETTINGS_KEYS: &[&str] = &[
+    "RetroAchievementsUser",
+    "RetroAchievementsApiKey",
+    "ScreenScraperUser",
+    "ScreenScraperPassword",
+    "ScreenScraperDevId",
+    "ScreenScraperDevPassword",
+];
+const FORMAT_KEYS: &[&str] = &["Loop", "Mute", "Start", "ImageDuration", "Scale"];
+// [Settings] keys of earlier releases that nothing reads any more, reported as such rather
+// than as typos
+const UNUSED_SETTINGS_KEYS: &[&str] = &["MPVTestCommand"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The configuration cannot be loaded, or a feature cannot work.
+    Error,
+    /// Something is probably wrong but the application still runs.
+    Warning,
+}
+
+impl Severity {
+    fn message_key(&self) -> &'static str {
+        match self {
+            Severity::Error => "check_error",
+            Severity::Warning => "check_warning",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Where the problem is, e.g. `[Settings] MPVPath` or `[Output:main]`.
+    pub key: String,
+    /// The catalog key of the message, rendered in the language it is shown in.
+    pub message: &'static str,
+    pub args: Vec<(&'static str, String)>,
+}
+
+impl Issue {
+    /// The message in the language of `messages`.
+    pub fn describe(&self, messages: &Catalog) -> String {
+        let args: Vec<(&str, &dyn Display)> = self.args.iter().map(|(name, value)| (*name, value as &dyn Display)).collect();
+        messages.format(self.message, &args)
+    }
+}
+
+/// Every problem found in a configuration, errors first.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    fn push(&mut self, severity: Severity, key: String, message: &'static str, args: &[(&'static str, &dyn Display)]) {
+        let args = args.iter().map(|(name, value)| (*name, value.to_string())).collect();
+        self.issues.push(Issue { severity, key, message, args });
+    }
+
+    fn error(&mut self, key: impl Into<String>, message: &'static str, args: &[(&'static str, &dyn Display)]) {
+        self.push(Severity::Error, key.into(), message, args);
+    }
+
+    fn warning(&mut self, key: impl Into<String>, message: &'static str, args: &[(&'static str, &dyn Display)]) {
+        self.push(Severity::Warning, key.into(), message, args);
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|issue| issue.severity == Severity::Error)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|issue| issue.severity == severity).count()
+    }
+}
+
+impl Report {
+    /// One line per issue then the totals, labelled in the language of `messages`.
+    pub fn describe(&self, messages: &Catalog) -> String {
+        let mut text = String::new();
+        for issue in &self.issues {
+            let severity = messages.get(issue.severity.message_key());
+            text += &format!("{:<7} {}: {}\n", severity, issue.key, issue.describe(messages));
+        }
+        let (errors, warnings) = (self.count(Severity::Error), self.count(Severity::Warning));
+        text + &messages.format("check_summary", &[("errors", &errors), ("warnings", &warnings)])
+    }
+
+    /// Logs every issue at the level of its severity, in the language of `messages`.
+    pub fn log(&self, messages: &Catalog) {
+        for issue in &self.issues {
+            match issue.severity {
+                Severity::Error => error!("Config {}: {}", issue.key, issue.describe(messages)),
+                Severity::Warning => warn!("Config {}: {}", issue.key, issue.describe(messages)),
+            }
+        }
+    }
+}
+
+/// Checks the configuration file at `path`: its sections and key names, its templates, then
+/// the values of the loaded configuration.
+pub fn check_file(path: &Path) -> Report {
+    match fs::read_to_string(path) {
+        Ok(content) => check_content(path, &content),
+        Err(e) => {
+            let mut report = Report::default();
+            report.error(path.display().to_string(), "check_unreadable", &[("error", &e)]);
+            report
+        }
+    }
+}
+
+/// Checks `content` as if it were the configuration file at `path`, e.g. before writing it.
+pub fn check_content(path: &Path, content: &str) -> Report {
+    let mut report = Report::default();
+    let sections: HashMap<String, HashMap<String, String>> = match serde_ini::from_str(content) {
+        Ok(sections) => sections,
+        Err(e) => {
+            report.error(path.display().to_string(), "check_not_ini", &[("error", &e)]);
+            return report;
+        }
+    };
+
+    check_structure(&sections, &mut report);
+    check_templates(&sections, &mut report);
+    check_version(content, &mut report);
+
+    // Loading reports the first problem only, so it is skipped when problems are already listed
+    if !report.has_errors() {
+        match Config::parse_config(path, content) {
+            Ok(config) => check_config(&config, &mut report),
+            Err(e) => report.error(path.display().to_string(), "check_not_loaded", &[("error", &e)]),
+        }
+    }
+    report.issues.sort_by_key(|issue| issue.severity);
+    report
+}
+
+fn check_structure(sections: &HashMap<String, HashMap<String, String>>, report: &mut Report) {
+    if !sections.contains_key("Settings") {
+        report.error("[Settings]", "check_section_missing", &[]);
+    }
+
+    let mut names: Vec<&String> = sections.keys().collect();
+    names.sort();
+    for name in names {
+        let section = &sections[name];
+        let (known_keys, unknown_severity): (Vec<&str>, Severity) = match name.split_once(':') {
+            None if name == "Settings" => {
+                let keys = config::settings_keys().iter().chain(SHARED_SETTINGS_KEYS).chain([&migrate::VERSION_KEY]);
+                (keys.copied().collect(), Severity::Warning)
+            }
+            None if name == "Commands" => (EVENTS.to_vec(), Severity::Warning),
+            Some(("Output", _)) => (OUTPUT_KEYS.iter().chain(EVENTS).copied().collect(), Severity::Error),
+            Some(("Format", _)) => (FORMAT_KEYS.to_vec(), Severity::Error),
+            Some(("System", _)) => (config::settings_keys().to_vec(), Severity::Warning),
+            _ => {
+                let prefix = name.split(':').next().unwrap_or(name);
+                let key = format!("[{}]", name);
+                match suggest(prefix, &["Settings", "Commands", "Output", "Format", "System"]) {
+                    Some(known) if name.contains(':') => {
+                        report.error(key, "check_unknown_section_suggestion", &[("section", &format!("{}:...", known))])
+                    }
+                    Some(known) => report.error(key, "check_unknown_section_suggestion", &[("section", &known)]),
+                    None => report.error(key, "check_unknown_section", &[]),
+                }
+                continue;
+            }
+        };
+
+        let mut keys: Vec<&String> = section.keys().collect();
+        keys.sort();
+        for key in keys.into_iter().filter(|key| !known_keys.contains(&key.as_str())) {
+            let key_name = format!("[{}] {}", name, key);
+            let settings = name == "Settings" || name.starts_with("System:");
+            if settings && UNUSED_SETTINGS_KEYS.contains(&key.as_str()) {
+                report.warning(key_name, "check_unused_key", &[]);
+                continue;
+            }
+            match suggest(key, &known_keys) {
+                Some(known) => report.push(unknown_severity, key_name, "check_unknown_key_suggestion", &[("key", &known)]),
+                None => report.push(unknown_severity, key_name, "check_unknown_key", &[]),
+            }
+        }
+    }
+}
+
+fn check_version(content: &str, report: &mut Report) {
+    let mut document = Document::parse(content);
+    match migrate::migrate(&mut document) {
+        Ok(changes) if changes.iter().any(Change::is_needed) => {
+            report.warning("[Settings] ConfigVersion", "check_old_version", &[])
+        }
+        Ok(_) => {}
+        Err(e) => report.error("[Settings] ConfigVersion", "check_invalid", &[("error", &e)]),
+    }
+}
+
+// Parses every template on its own, so all placeholder mistakes are listed at once
+fn check_templates(sections: &HashMap<String, HashMap<String, String>>, report: &mut Report) {
+    if let Some(settings) = sections.get("Settings") {
+        let templates: &[(&str, &[&str], Escape)] = &[
+            ("MPVLaunchCommand", template::LAUNCH_PLACEHOLDERS, Escape::Args),
+            ("MPVKillCommand", template::KILL_PLACEHOLDERS, Escape::Shell),
+            ("MarqueeFilePath", template::GAME_FILE_PLACEHOLDERS, Escape::Raw),
+            ("MarqueeFilePathDefault", template::GAME_FILE_PLACEHOLDERS, Escape::Raw),
+            ("SystemFilePath", template::SYSTEM_FILE_PLACEHOLDERS, Escape::Raw),
+            ("CollectionFilePath", template::COLLECTION_FILE_PLACEHOLDERS, Escape::Raw),
+            ("GeneratorEncoderCommand", template::ENCODER_PLACEHOLDERS, Escape::Shell),
+            ("OverlayTemplate", template::OVERLAY_PLACEHOLDERS, Escape::Raw),
+        ];
+        for (key, known, escape) in templates {
+            if let Some(source) = settings.get(*key) {
+                if let Err(e) = Template::parse(key, source, known, *escape) {
+                    template_error(report, format!("[Settings] {}", key), &e);
+                }
+            }
+        }
+    }
+
+    if let Some(commands) = sections.get("Commands") {
+        for (event, source) in commands {
+            if let Err(e) = Action::parse(event, source) {
+                template_error(report, format!("[Commands] {}", event), &e);
+            }
+        }
+    }
+
+    for (name, section) in sections.iter().filter(|(name, _)| name.starts_with("Output:")) {
+        if let Some(source) = section.get("MPVLaunchCommand") {
+            if let Err(e) = Template::parse("MPVLaunchCommand", source, template::LAUNCH_PLACEHOLDERS, Escape::Args) {
+                template_error(report, format!("[{}] MPVLaunchCommand", name), &e);
+            }
+        }
+    }
+}
+
+fn template_error(report: &mut Report, key: String, error: &TemplateError) {
+    if let TemplateError::UnknownPlaceholder { name, known, .. } = error {
+        let known: Vec<&str> = known.iter().map(|k| k.as_str()).collect();
+        if let Some(suggestion) = suggest(name, &known) {
+            let (name, suggestion) = (format!("{{{}}}", name), format!("{{{}}}", suggestion));
+            report.error(key, "check_unknown_placeholder", &[("placeholder", &name), ("suggestion", &suggestion)]);
+            return;
+        }
+    }
+    report.error(key, "check_invalid", &[("error", error)]);
+}
+
+/// Checks the values of a loaded configuration: files that must exist and numbers that must
+/// make sense, in [Settings] then in each [System:<name>] override.
+pub fn check_config(config: &Config, report: &mut Report) {
+    let mut base = Report::default();
+    check_settings(config, "Settings", &mut base);
+
+    let mut systems = Report::default();
+    let mut system_names: Vec<&String> = config.system_configs.keys().collect();
+    system_names.sort();
+    for system_name in system_names {
+        check_settings(&config.system_configs[system_name], &format!("System:{}", system_name), &mut systems);
+    }
+    // Values a system inherits from [Settings] are only reported there
+    let inherited = |issue: &Issue| base.issues.iter().any(|base_issue| same_problem(base_issue, issue));
+    systems.issues.retain(|issue| !inherited(issue));
+    report.issues.extend(base.issues);
+    report.issues.extend(systems.issues);
+}
+
+// Whether two issues are the same problem with the same setting, whatever its section
+fn same_problem(a: &Issue, b: &Issue) -> bool {
+    let setting = |issue: &Issue| issue.key.split_once("] ").map(|(_, setting)| setting.to_string());
+    a.message == b.message && a.args == b.args && setting(a) == setting(b)
+}
+
+// The checks of check_config on the settings of one section, `Settings` or `System:<name>`
+fn check_settings(config: &Config, section: &str, report: &mut Report) {
+    let settings = &config.settings;
+    let key = |setting: &str| format!("[{}] {}", section, setting);
+
+    let uses_mpv = config.outputs.iter().any(|output| output.backend == Backend::Mpv);
+    if uses_mpv && !settings.mpv_path.is_file() {
+        report.error(key("MPVPath"), "check_file_missing", &[("path", &format!("{:?}", settings.mpv_path))]);
+    }
+    if !settings.default_image_path.is_file() {
+        let path = format!("{:?}", settings.default_image_path);
+        report.warning(key("DefaultImagePath"), "check_file_missing", &[("path", &path)]);
+    }
+    let folders = [
+        ("RomsPath", Some(&settings.roms_path)),
+        ("RetroBatPath", settings.retrobat_path.as_ref()),
+        ("MarqueeImagePath", Some(&settings.marquee_image_path)),
+        ("MarqueeImagePathDefault", Some(&settings.marquee_image_path_default)),
+        ("SystemMarqueePath", Some(&settings.system_marquee_path)),
+        ("CollectionMarqueePath", Some(&settings.collection_marquee_path)),
+    ];
+    for (setting, folder) in folders {
+        if let Some(folder) = folder.filter(|folder| !folder.is_dir()) {
+            report.warning(key(setting), "check_folder_missing", &[("path", &format!("{:?}", folder))]);
+        }
+    }
+
+    if settings.marquee_width <= 0 {
+        report.error(key("MarqueeWidth"), "check_not_positive", &[("value", &settings.marquee_width)]);
+    }
+    if settings.marquee_height <= 0 {
+        report.error(key("MarqueeHeight"), "check_not_positive", &[("value", &settings.marquee_height)]);
+    }
+    if settings.marquee_border < 0 || settings.marquee_border.saturating_mul(2) >= settings.marquee_width.min(settings.marquee_height) {
+        report.warning(key("MarqueeBorder"), "check_border_too_wide", &[("value", &settings.marquee_border)]);
+    }
+    if settings.screen_number < 0 {
+        report.error(key("ScreenNumber"), "check_negative", &[("value", &settings.screen_number)]);
+    }
+    if !(1..=65535).contains(&settings.port) {
+        report.error(key("port"), "check_port_range", &[("value", &settings.port)]);
+    }
+    if settings.overlay_font_size <= 0 {
+        report.error(key("OverlayFontSize"), "check_not_positive", &[("value", &settings.overlay_font_size)]);
+    }
+    if settings.overlay_duration < 0 {
+        report.error(key("OverlayDuration"), "check_negative", &[("value", &settings.overlay_duration)]);
+    }
+    if let Some(offset) = settings.generator_fanart_offset.filter(|offset| !(0.0..=1.0).contains(offset)) {
+        report.warning(key("GeneratorFanartOffset"), "check_fanart_offset_range", &[("value", &offset)]);
+    }
+    if settings.accepted_formats.split(',').any(|ext| ext.trim().is_empty()) {
+        report.warning(key("AcceptedFormats"), "check_empty_format", &[]);
+    }
+    for format in config.formats.formats.iter().filter(|format| format.kind.is_none()) {
+        report.warning(key("AcceptedFormats"), "check_unknown_format", &[("format", &format.extension)]);
+    }
+}
+
+// The known name closest to a misspelt one, if it is close enough to be a typo
+fn suggest<'a>(name: &str, known: &[&'a str]) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(2);
+    known
+        .iter()
+        .map(|candidate| (levenshtein(&name, &candidate.to_lowercase()), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -1,105 +1,68 @@
-use serde::{de::{self, Deserializer}, Deserialize};
-use std::collections::HashMap;
+use crate::messages;
+use marquee_core::config::{find_retrobat_path, plugin_path, DEFAULT_IPC_CHANNEL};
+use marquee_core::es_settings::{self, EsSettings};
+use marquee_core::i18n::Catalog;
+use serde::Deserialize;
 use std::path::{Path, PathBuf};
 use std::fs;
 
-// Custom deserializer for boolean values that might be strings
-fn deserialize_bool_from_string<'de, D>(deserializer: D) -> Result<bool, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: String = String::deserialize(deserializer)?;
-    match s.to_lowercase().as_str() {
-        "true" => Ok(true),
-        "false" => Ok(false),
-        _ => Err(de::Error::unknown_variant(&s, &["true", "false"])),
-    }
+fn default_ipc_channel() -> String {
+    DEFAULT_IPC_CHANNEL.to_string()
 }
 
+// Only the keys this module reads: config.ini is shared with marquee_manager_rs, which owns
+// the other keys. Language and the username default to EmulationStation's settings.
 #[derive(Deserialize, Debug, Clone)]
 pub struct Settings {
-    #[serde(rename = "Language")]
-    pub language: String,
-    #[serde(rename = "MarqueeWidth")]
-    pub marquee_width: i32,
-    #[serde(rename = "MarqueeHeight")]
-    pub marquee_height: i32,
-    #[serde(rename = "MarqueeBorder")]
-    pub marquee_border: i32,
-    #[serde(rename = "MarqueeAutoConvert", deserialize_with = "deserialize_bool_from_string")]
-    pub marquee_auto_convert: bool,
-    #[serde(rename = "AcceptedFormats")]
-    pub accepted_formats: String,
+    #[serde(rename = "Language", default)]
+    pub language: Option<String>,
+    #[serde(rename = "RetroAchievementsUser", default)]
+    pub retroachievements_user: Option<String>,
+    /// The web API key from the RetroAchievements control panel, which RetroBat does not store.
+    #[serde(rename = "RetroAchievementsApiKey", default)]
+    pub retroachievements_api_key: Option<String>,
     #[serde(rename = "RetroBatPath")]
-    pub retrobat_path: PathBuf,
-    #[serde(rename = "RomsPath")]
-    pub roms_path: PathBuf,
-    #[serde(rename = "DefaultImagePath")]
-    pub default_image_path: PathBuf,
-    #[serde(rename = "MarqueeImagePath")]
-    pub marquee_image_path: PathBuf,
-    #[serde(rename = "MarqueeFilePath")]
-    pub marquee_file_path: String,
-    #[serde(rename = "MarqueeImagePathDefault")]
-    pub marquee_image_path_default: PathBuf,
-    #[serde(rename = "MarqueeFilePathDefault")]
-    pub marquee_file_path_default: String,
-    #[serde(rename = "MarqueeAutoScraping", deserialize_with = "deserialize_bool_from_string")]
-    pub marquee_auto_scraping: bool,
-    #[serde(rename = "MarqueeAutoScrapingDebug", deserialize_with = "deserialize_bool_from_string")]
-    pub marquee_auto_scraping_debug: bool,
-    #[serde(rename = "SystemMarqueePath")]
-    pub system_marquee_path: PathBuf,
-    #[serde(rename = "SystemFilePath")]
-    pub system_file_path: String,
-    #[serde(rename = "CollectionMarqueePath")]
-    pub collection_marquee_path: PathBuf,
-    #[serde(rename = "CollectionFilePath")]
-    pub collection_file_path: String,
-    #[serde(rename = "CollectionAlternativNames")]
-    pub collection_alternativ_names: String,
-    #[serde(rename = "CollectionCorrelation")]
-    pub collection_correlation: String,
-    #[serde(rename = "IPCChannel")]
+    pub retrobat_path: Option<PathBuf>,
+    #[serde(rename = "IPCChannel", default = "default_ipc_channel")]
     pub ipc_channel: String,
-    #[serde(rename = "ScreenNumber")]
-    pub screen_number: i32,
-    #[serde(rename = "MPVPath")]
-    pub mpv_path: PathBuf,
-    #[serde(rename = "MPVLaunchCommand")]
-    pub mpv_launch_command: String,
-    #[serde(rename = "MPVKillCommand")]
-    pub mpv_kill_command: String,
-    #[serde(rename = "MPVTestCommand")]
-    pub mpv_test_command: String,
-    #[serde(rename = "IMPath")]
-    pub im_path: PathBuf,
-    #[serde(rename = "IMConvertCommand")]
-    pub im_convert_command: String,
-    pub host: String,
-    pub port: i32,
-    #[serde(rename = "logFile", deserialize_with = "deserialize_bool_from_string")]
-    pub log_file: bool,
-}
-
-#[derive(Deserialize, Debug, Clone)]
-pub struct Commands {
-    #[serde(flatten)]
-    pub commands: HashMap<String, String>,
+    #[serde(rename = "ESSettingsPath", default)]
+    pub es_settings_path: Option<PathBuf>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     #[serde(rename = "Settings")]
     pub settings: Settings,
-    #[serde(rename = "Commands")]
-    pub commands: Commands,
+    /// The console messages in the configured `Language`.
+    #[serde(skip)]
+    pub messages: Catalog,
 }
 
 impl Config {
     pub fn load_config(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
         let content = fs::read_to_string(path)?;
-        let config = serde_ini::from_str(&content)?;
+        let mut config: Config = serde_ini::from_str(&content)?;
+        let plugin_path = plugin_path(path)?;
+        config.settings.retrobat_path = find_retrobat_path(config.settings.retrobat_path.take())
+            .map(|retrobat_path| plugin_path.join(retrobat_path));
+
+        let settings = &config.settings;
+        let frontend = match es_settings::resolve_path(
+            settings.es_settings_path.as_deref(),
+            settings.retrobat_path.as_deref(),
+            &plugin_path,
+        ) {
+            Ok(es_settings_path) => EsSettings::load_or_default(&es_settings_path),
+            Err(_) => EsSettings::default(),
+        };
+        let settings = &mut config.settings;
+        if settings.language.is_none() {
+            settings.language = frontend.language().map(str::to_string);
+        }
+        if settings.retroachievements_user.is_none() {
+            settings.retroachievements_user = frontend.retroachievements().map(|login| login.username);
+        }
+        config.messages = messages::catalog(config.settings.language.as_deref().unwrap_or_default());
         Ok(config)
     }
 }
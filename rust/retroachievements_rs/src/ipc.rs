@@ -1,20 +1,9 @@
 use crate::api::{GameInfoAndUserProgress, UserProfile};
 use crate::config::Config;
-use std::fs::OpenOptions;
-use std::io::Write;
+use marquee_core::ipc::send_command;
 
 pub fn send_to_mpv(data: &str, config: &Config) {
-    if let Ok(mut file) = OpenOptions::new()
-        .write(true)
-        .open(&config.settings.ipc_channel)
-    {
-        let command = format!("script-message ra-data {}\n", data);
-        if let Err(e) = file.write_all(command.as_bytes()) {
-            eprintln!("Failed to write to MPV pipe: {}", e);
-        }
-    } else {
-        eprintln!("Failed to open MPV pipe at {}", config.settings.ipc_channel);
-    }
+    send_command(&config.settings.ipc_channel, &format!("script-message ra-data {}", data));
 }
 
 pub fn format_user_profile(profile: &UserProfile) -> String {
@@ -2,63 +2,81 @@ mod api;
 mod config;
 mod ipc;
 mod log_monitor;
-mod systems; // Although unused, we keep it for consistency with the main app
+mod messages;
 
 use crate::config::Config;
-use std::path::Path;
+use marquee_core::i18n::FALLBACK_LANGUAGE;
 use std::sync::mpsc::channel;
 use std::thread;
 
 #[tokio::main]
 async fn main() {
+    env_logger::init();
+
     // Load configurations
-    let config_path = Path::new("config.ini");
-    let config = match Config::load_config(config_path) {
+    let config_path = marquee_core::config::locate_config(None);
+    let config = match Config::load_config(&config_path) {
         Ok(c) => c,
         Err(e) => {
-            eprintln!("Failed to load config: {}", e);
+            let messages = messages::catalog(FALLBACK_LANGUAGE);
+            eprintln!("{}", messages.format("config_load_failed", &[("error", &e)]));
             return;
         }
     };
-    println!("Config loaded successfully.");
+    let messages = config.messages.clone();
+    println!("{}", messages.get("config_loaded"));
 
-    // In a real scenario, the username and API key would come from es_settings.cfg
-    let api_client = api::ApiClient::new("testuser".to_string(), "testkey".to_string());
+    let Some(username) = config.settings.retroachievements_user.clone() else {
+        eprintln!("{}", messages.get("username_missing"));
+        return;
+    };
+    let Some(api_key) = config.settings.retroachievements_api_key.clone() else {
+        eprintln!("{}", messages.get("api_key_missing"));
+        return;
+    };
+    let api_client = api::ApiClient::new(username.clone(), api_key);
 
     // Start the log monitor
     let (tx, rx) = channel();
-    let log_path = config.settings.retrobat_path.join("emulators/retroarch/logs/retroarch.log");
+    let log_path = match &config.settings.retrobat_path {
+        Some(retrobat_path) => retrobat_path.join("emulators/retroarch/logs/retroarch.log"),
+        None => {
+            eprintln!("{}", messages.get("retrobat_path_missing"));
+            return;
+        }
+    };
+    let log_messages = messages.clone();
     let _log_thread = thread::spawn(move || {
         if let Err(e) = log_monitor::start_watching(&log_path, tx) {
-            eprintln!("Error in log monitor: {}", e);
+            eprintln!("{}", log_messages.format("log_monitor_failed", &[("error", &e)]));
         }
     });
 
-    println!("RetroAchievements monitor is running.");
+    println!("{}", messages.get("running"));
 
     // Main event loop
     loop {
         if let Ok(log_event) = rx.recv() {
             match log_event {
                 log_monitor::LogEvent::GameIdentified(id) => {
-                    println!("Game identified: {}", id);
-                    match api_client.get_user_profile("TestUser").await {
+                    println!("{}", messages.format("game_identified", &[("id", &id)]));
+                    match api_client.get_user_profile(&username).await {
                         Ok(profile) => {
                             let formatted_profile = ipc::format_user_profile(&profile);
                             ipc::send_to_mpv(&formatted_profile, &config);
                         }
-                        Err(e) => eprintln!("Failed to get user profile: {}", e),
+                        Err(e) => eprintln!("{}", messages.format("profile_failed", &[("error", &e)])),
                     }
-                    match api_client.get_game_info_and_user_progress(id, "TestUser").await {
+                    match api_client.get_game_info_and_user_progress(id, &username).await {
                         Ok(game_info) => {
                             let formatted_game_info = ipc::format_game_info(&game_info);
                             ipc::send_to_mpv(&formatted_game_info, &config);
                         }
-                        Err(e) => eprintln!("Failed to get game info: {}", e),
+                        Err(e) => eprintln!("{}", messages.format("game_info_failed", &[("error", &e)])),
                     }
                 }
                 log_monitor::LogEvent::AchievementUnlocked(id) => {
-                    println!("Achievement unlocked: {}", id);
+                    println!("{}", messages.format("achievement_unlocked", &[("id", &id)]));
                     let notification = format!("achievement|{}", id);
                     ipc::send_to_mpv(&notification, &config);
                 }
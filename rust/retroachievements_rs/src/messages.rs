@@ -0,0 +1,6 @@
+use marquee_core::i18n::Catalog;
+
+/// The messages of `language`, the `Language` setting.
+pub fn catalog(language: &str) -> Catalog {
+    Catalog::new(language, marquee_core::builtin_catalogs!())
+}
@@ -0,0 +1,58 @@
+use marquee_core::config::{find_retrobat_path, plugin_path};
+use marquee_core::es_settings::{self, Credentials, EsSettings};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Only the keys this module reads: config.ini is shared with marquee_manager_rs, which owns
+// the other keys. The ScreenScraper login defaults to EmulationStation's settings.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Settings {
+    #[serde(rename = "RetroBatPath")]
+    pub retrobat_path: Option<PathBuf>,
+    #[serde(rename = "ESSettingsPath", default)]
+    pub es_settings_path: Option<PathBuf>,
+    #[serde(rename = "ScreenScraperUser", default)]
+    pub screenscraper_user: Option<String>,
+    #[serde(rename = "ScreenScraperPassword", default)]
+    pub screenscraper_password: Option<String>,
+    /// The developer login ScreenScraper gives to applications, sent with every request.
+    #[serde(rename = "ScreenScraperDevId", default)]
+    pub screenscraper_dev_id: Option<String>,
+    #[serde(rename = "ScreenScraperDevPassword", default)]
+    pub screenscraper_dev_password: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    #[serde(rename = "Settings")]
+    pub settings: Settings,
+}
+
+impl Config {
+    pub fn load_config(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let mut config: Config = serde_ini::from_str(&content)?;
+        let plugin_path = plugin_path(path)?;
+        let settings = &mut config.settings;
+        settings.retrobat_path =
+            find_retrobat_path(settings.retrobat_path.take()).map(|retrobat_path| plugin_path.join(retrobat_path));
+        // Left unset when it cannot be resolved, the frontend's login is then not looked up
+        settings.es_settings_path = es_settings::resolve_path(
+            settings.es_settings_path.as_deref(),
+            settings.retrobat_path.as_deref(),
+            &plugin_path,
+        )
+        .ok();
+        Ok(config)
+    }
+
+    /// The user's ScreenScraper login: config.ini's, or else the one saved by EmulationStation.
+    pub fn screenscraper_login(&self) -> Option<Credentials> {
+        let settings = &self.settings;
+        if let Some(username) = &settings.screenscraper_user {
+            return Some(Credentials { username: username.clone(), password: settings.screenscraper_password.clone() });
+        }
+        EsSettings::load_or_default(settings.es_settings_path.as_ref()?).screenscraper()
+    }
+}
@@ -1,8 +1,10 @@
+mod config;
 mod scraper;
 mod ws;
 
+use crate::config::Config;
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use marquee_core::config::{locate_config, plugin_path};
 use url::Url;
 
 #[derive(Parser)]
@@ -31,8 +33,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     match &cli.command {
         Commands::Scraper => {
-            let pool_file = PathBuf::from("scrap.pool");
-            scraper::run(&pool_file).await?;
+            let config_path = locate_config(None);
+            let config = Config::load_config(&config_path)?;
+            // The pool lives in the plugin folder, next to config.ini
+            let pool_file = plugin_path(&config_path)?.join("scrap.pool");
+            scraper::run(&pool_file, &config).await?;
         }
         Commands::MameWs => {
             let url = Url::parse("ws://127.0.0.1:8080")?;
@@ -1,3 +1,4 @@
+use crate::config::Config;
 use reqwest::Client;
 use serde::Deserialize;
 use std::fs;
@@ -5,6 +6,8 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 use futures_util::StreamExt;
 
+const API_URL: &str = "https://www.screenscraper.fr/api2/jeuInfos.php";
+
 #[derive(Debug, Deserialize)]
 struct ScrapEntry {
     system_name: String,
@@ -37,8 +40,9 @@ struct Media {
     url: String,
 }
 
-pub async fn run(pool_file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn run(pool_file: &Path, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     let client = Client::new();
+    let auth = auth_params(config);
     let content = fs::read_to_string(pool_file)?;
     let entries: Vec<ScrapEntry> = content
         .lines()
@@ -61,7 +65,7 @@ pub async fn run(pool_file: &Path) -> Result<(), Box<dyn std::error::Error>> {
 
     for entry in entries {
         println!("Scraping marquee for: {}", entry.game_title);
-        if let Err(e) = download_marquee(&client, &entry).await {
+        if let Err(e) = download_marquee(&client, &auth, &entry).await {
             eprintln!("Failed to download marquee for {}: {}", entry.game_title, e);
         }
     }
@@ -69,13 +73,34 @@ pub async fn run(pool_file: &Path) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn download_marquee(client: &Client, entry: &ScrapEntry) -> Result<(), Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://www.screenscraper.fr/api2/jeuInfos.php?devid=YOUR_DEV_ID&devpassword=YOUR_DEV_PASS&softname=MarqueeManager&output=json&romnom={}&systemeid=0",
-        entry.game_name
-    );
+// The developer and user logins sent with every request, leaving out the ones not configured
+fn auth_params(config: &Config) -> Vec<(&'static str, String)> {
+    let settings = &config.settings;
+    let login = config.screenscraper_login();
+    [
+        ("devid", settings.screenscraper_dev_id.clone()),
+        ("devpassword", settings.screenscraper_dev_password.clone()),
+        ("ssid", login.as_ref().map(|login| login.username.clone())),
+        ("sspassword", login.and_then(|login| login.password)),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name, value?)))
+    .collect()
+}
 
-    let resp: ScreenScraperResponse = client.get(&url).send().await?.json().await?;
+async fn download_marquee(
+    client: &Client,
+    auth: &[(&str, String)],
+    entry: &ScrapEntry,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let resp: ScreenScraperResponse = client
+        .get(API_URL)
+        .query(auth)
+        .query(&[("softname", "MarqueeManager"), ("output", "json"), ("romnom", &entry.game_name), ("systemeid", "0")])
+        .send()
+        .await?
+        .json()
+        .await?;
     if let Some(marquee_media) = resp.response.jeu.medias.iter().find(|m| m.media_type == "marquee") {
         let mut stream = client.get(&marquee_media.url).send().await?.bytes_stream();
         let target_path = PathBuf::from(&entry.full_marquee_path);